@@ -11,23 +11,31 @@ use std::fs;
 //12
 //Then we explore the 2 node twice unless we do BFS.
 
+//Part 2 solution notes:
+//Walking every trail is exponential when there are lots of them. Instead, the rating of a location is the sum
+//of the ratings of the locations one higher next to it (and a 9 has a rating of 1).
+//So going from the 9's down to the 0's we rate every location exactly once. Scores work the same way,
+//with the set of 9's reachable from a location instead.
+
 pub mod topographic_map {
 
+    use std::collections::BTreeMap;
     use std::collections::HashMap;
     use std::collections::HashSet;
     use std::collections::VecDeque;
+    use std::ops::RangeInclusive;
 
     //We use Matrix as helper struct to TopMap
-    ///A Matrix holds a vector of vectors of usize
+    ///A Matrix holds a vector of vectors of heights, where None is an impassable location
     struct Matrix {
-        data: Vec<Vec<usize>>,
+        data: Vec<Vec<Option<usize>>>,
         row_num: usize,
         col_num: usize,
     }
 
     impl Matrix {
         //Since the data is guaranteed to be rectanguler we can deduce row_num and col_num from data
-        fn new(data: Vec<Vec<usize>>) -> Result<Matrix, &'static str> {
+        fn new(data: Vec<Vec<Option<usize>>>) -> Result<Matrix, &'static str> {
             if data.is_empty() {
                 return Err("empty input");
             }
@@ -43,30 +51,193 @@ pub mod topographic_map {
         }
     }
 
+    ///The rules of what makes a hiking trail
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub struct TrailRules {
+        ///The allowed differences in height from one location of a trail to the next.
+        ///They must be all positive or all negative, so a trail can never go around in circles.
+        pub height_delta: RangeInclusive<isize>,
+        ///If true a trail can also go diagonally (so each location has 8 neighbors instead of 4)
+        pub diagonal: bool,
+        ///The height of a trailhead
+        pub start_height: usize,
+        ///The height of the end of a trail (a trail ends as soon as it reaches it)
+        pub end_height: usize,
+    }
+
+    impl Default for TrailRules {
+        ///The rules from the puzzle: from a 0 to a 9, going up by exactly 1 up, down, left or right
+        fn default() -> TrailRules {
+            TrailRules {
+                height_delta: 1..=1,
+                diagonal: false,
+                start_height: 0,
+                end_height: 9,
+            }
+        }
+    }
+
+    impl TrailRules {
+        ///Whether trails go up (rather than down)
+        fn ascending(&self) -> bool {
+            *self.height_delta.start() > 0
+        }
+
+        ///The offsets (row, col) to the neighbors of a location
+        fn neighbor_offsets(&self) -> &'static [(isize, isize)] {
+            const STRAIGHT: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, 1), (0, -1)];
+            const ALL: [(isize, isize); 8] = [
+                (-1, 0),
+                (1, 0),
+                (0, 1),
+                (0, -1),
+                (-1, 1),
+                (1, 1),
+                (1, -1),
+                (-1, -1),
+            ];
+
+            if self.diagonal { &ALL } else { &STRAIGHT }
+        }
+    }
+
+    ///A trailhead on a TopMap, for inspecting where its hiking trails go
+    pub struct Trailhead<'a> {
+        pub location: (usize, usize),
+        map: &'a TopMap,
+    }
+
+    impl<'a> Trailhead<'a> {
+        ///Returns the locations of the ends of trails reachable from this trailhead (in reading order)
+        pub fn summits(&self) -> Vec<(usize, usize)> {
+            let mut summits: Vec<_> = self
+                .map
+                .reachable_summits(self.location)
+                .into_iter()
+                .collect();
+            summits.sort();
+            summits
+        }
+
+        ///Returns every distinct hiking trail starting at this trailhead, as the list of locations along it.
+        ///The trails are found lazily (one at a time) and at most limit of them are returned, since a dense map
+        ///can have a huge number of them.
+        pub fn trails(&self, limit: usize) -> impl Iterator<Item = Vec<(usize, usize)>> + 'a {
+            Trails {
+                map: self.map,
+                stack: vec![vec![self.location]],
+            }
+            .take(limit)
+        }
+    }
+
+    ///An iterator over the hiking trails from a trailhead, using depth-first search
+    struct Trails<'a> {
+        map: &'a TopMap,
+        //the partial trails we have yet to explore
+        stack: Vec<Vec<(usize, usize)>>,
+    }
+
+    impl Iterator for Trails<'_> {
+        type Item = Vec<(usize, usize)>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while let Some(trail) = self.stack.pop() {
+                let end = *trail.last().expect("a trail is never empty");
+                if self.map.is_trail_end(end) {
+                    return Some(trail);
+                }
+
+                //We push in reverse so that trails are returned in the order of next_steps
+                for neighbor in self.map.next_steps(end).into_iter().rev() {
+                    let mut longer_trail = trail.clone();
+                    longer_trail.push(neighbor);
+                    self.stack.push(longer_trail);
+                }
+            }
+
+            None
+        }
+    }
+
+    ///The error when a rating is too big to count
+    const TOO_MANY_TRAILS: &str = "there are more trails than fit in a usize";
+
+    ///A set of locations for each location
+    type LocationSets = HashMap<(usize, usize), HashSet<(usize, usize)>>;
+
+    ///The rating and score of every location on a TopMap
+    pub struct TrailCounts {
+        pub ratings: Vec<Vec<usize>>,
+        pub scores: Vec<Vec<usize>>,
+    }
+
     pub struct TopMap {
         trail_head_locations: HashSet<(usize, usize)>,
         map: Matrix,
+        rules: TrailRules,
     }
 
     impl TopMap {
         pub fn build(map: Vec<Vec<usize>>) -> TopMap {
-            let map = Matrix::new(map).unwrap();
-            let trail_head_locations = TopMap::find_trailheads(&map).unwrap();
+            let map = map
+                .into_iter()
+                .map(|row| row.into_iter().map(Some).collect())
+                .collect();
+            TopMap::build_with_rules(map, TrailRules::default()).unwrap()
+        }
+
+        ///Builds a map where None is an impassable location, with hiking trails following rules
+        pub fn build_with_rules(
+            map: Vec<Vec<Option<usize>>>,
+            rules: TrailRules,
+        ) -> Result<TopMap, &'static str> {
+            let (first, last) = (*rules.height_delta.start(), *rules.height_delta.end());
+            if first > last || (first <= 0 && last >= 0) {
+                return Err("the height delta range must be all positive or all negative");
+            }
+
+            let map = Matrix::new(map)?;
+            let trail_head_locations = TopMap::find_trailheads(&map, rules.start_height)?;
 
-            TopMap {
+            Ok(TopMap {
                 trail_head_locations,
                 map,
-            }
+                rules,
+            })
+        }
+
+        ///Parses a map of digits, where '.' is an impassable location
+        pub fn parse(input: &str, rules: TrailRules) -> Result<TopMap, &'static str> {
+            let map = input
+                .lines()
+                .map(|line| {
+                    line.chars()
+                        .map(|char| match char {
+                            '.' => Ok(None),
+                            _ => char
+                                .to_digit(10)
+                                .map(|digit| Some(digit as usize))
+                                .ok_or("Map should be composed of digits 0-9 and '.'"),
+                        })
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?;
+
+            TopMap::build_with_rules(map, rules)
         }
 
-        ///Find the locations of the trailheads (0's) in a map.
-        ///That is find indices (i,j) such that self.map at (i,j) == 0
-        fn find_trailheads(map: &Matrix) -> Result<HashSet<(usize, usize)>, &'static str> {
+        ///Find the locations of the trailheads in a map.
+        ///That is find indices (i,j) such that self.map at (i,j) == start_height
+        fn find_trailheads(
+            map: &Matrix,
+            start_height: usize,
+        ) -> Result<HashSet<(usize, usize)>, &'static str> {
             let mut locations: HashSet<(usize, usize)> = HashSet::new();
 
             for (row_index, row) in map.data.iter().enumerate() {
                 for (col_index, entry) in row.iter().enumerate() {
-                    if *entry == 0 {
+                    if *entry == Some(start_height) {
                         locations.insert((row_index, col_index));
                     }
                 }
@@ -81,251 +252,251 @@ pub mod topographic_map {
 
         ///Returns the sum of the scores of all trailheads on a topographic map.
         pub fn score_map(&self) -> usize {
-            //We score each individual trailhead and sum their scores
+            let scores = self.scores();
             self.trail_head_locations
                 .iter()
-                .map(|trailhead_location| self.score_trailhead(*trailhead_location))
+                .map(|location| scores[location.0][location.1])
                 .sum()
         }
 
-        ///Returns the number of 9's reachable from this location according to the hiking rules.
-        fn score_trailhead(&self, head_location: (usize, usize)) -> usize {
-            //We do Breadth-first search. See reasons why at the top of this file
-            let mut queue = VecDeque::new();
-
-            //The locations of the 9's reachable from this head location
-            let mut end_of_trail_locations = HashSet::new();
+        ///Returns the trailheads on the map (in reading order)
+        pub fn trailheads(&self) -> Vec<Trailhead<'_>> {
+            let mut locations: Vec<_> = self.trail_head_locations.iter().copied().collect();
+            locations.sort();
+            locations
+                .into_iter()
+                .map(|location| Trailhead {
+                    location,
+                    map: self,
+                })
+                .collect()
+        }
 
-            let mut explored = HashSet::new();
+        fn height(&self, location: (usize, usize)) -> Option<usize> {
+            self.map.data[location.0][location.1]
+        }
 
-            queue.push_back(head_location);
+        fn is_trail_end(&self, location: (usize, usize)) -> bool {
+            self.height(location) == Some(self.rules.end_height)
+        }
 
-            explored.insert(head_location);
+        ///Returns the neighbors of location (up, down, right, left and then the diagonals) a trail can go to
+        ///from location according to the rules. A trail ends at end_height, so it can't go anywhere from there.
+        fn next_steps(&self, location: (usize, usize)) -> Vec<(usize, usize)> {
+            let Some(height) = self.height(location) else {
+                return Vec::new();
+            };
+            if height == self.rules.end_height {
+                return Vec::new();
+            }
 
-            while let Some(location) = queue.pop_front() {
-                //see if one space up, down, left, or right from the location has a value greater by exactly one
-                //from the current location, if so enqueue it.
-                //If that value happens to be a 9 then just insert it to end_of_trail_locations instead.
-                let next_value = self.map.data[location.0][location.1] + 1;
-
-                let trail_end = next_value == 9;
-
-                //Look up. Note this is safe as && short-circuits in Rust
-                //(the RHS is evaluated only if  location.0  > 0 is true).
-                //Using explored allows us to avoid visiting nodes repeatdly.
-                if location.0 > 0
-                    && self.map.data[location.0 - 1][location.1] == next_value
-                    && explored.insert((location.0 - 1, location.1))
-                {
-                    if !trail_end {
-                        queue.push_back((location.0 - 1, location.1));
-                    } else {
-                        end_of_trail_locations.insert((location.0 - 1, location.1));
+            self.rules
+                .neighbor_offsets()
+                .iter()
+                .filter_map(|&(row_offset, col_offset)| {
+                    let row_index = location.0.checked_add_signed(row_offset)?;
+                    let col_index = location.1.checked_add_signed(col_offset)?;
+                    if row_index >= self.map.row_num || col_index >= self.map.col_num {
+                        return None;
                     }
-                }
 
-                //Look down
-                if location.0 + 1 < self.map.row_num
-                    && self.map.data[location.0 + 1][location.1] == next_value
-                    && explored.insert((location.0 + 1, location.1))
-                {
-                    if !trail_end {
-                        queue.push_back((location.0 + 1, location.1));
-                    } else {
-                        end_of_trail_locations.insert((location.0 + 1, location.1));
-                    }
-                }
+                    let neighbor_height = self.height((row_index, col_index))?;
+                    let delta = neighbor_height as isize - height as isize;
+                    self.rules
+                        .height_delta
+                        .contains(&delta)
+                        .then_some((row_index, col_index))
+                })
+                .collect()
+        }
 
-                //Look Right
-                if location.1 + 1 < self.map.col_num
-                    && self.map.data[location.0][location.1 + 1] == next_value
-                    && explored.insert((location.0, location.1 + 1))
-                {
-                    if !trail_end {
-                        queue.push_back((location.0, location.1 + 1));
-                    } else {
-                        end_of_trail_locations.insert((location.0, location.1 + 1));
-                    }
-                }
+        ///Renders the map with only the locations on trail shown (every other location is '.'),
+        ///like the example trails in the puzzle. Heights that aren't a single digit are shown as '#'.
+        pub fn render_trail(&self, trail: &[(usize, usize)]) -> String {
+            let trail: HashSet<&(usize, usize)> = trail.iter().collect();
+
+            (0..self.map.row_num)
+                .map(|row_index| {
+                    (0..self.map.col_num)
+                        .map(|col_index| match self.height((row_index, col_index)) {
+                            Some(height) if trail.contains(&(row_index, col_index)) => {
+                                u32::try_from(height)
+                                    .ok()
+                                    .and_then(|height| char::from_digit(height, 10))
+                                    .unwrap_or('#')
+                            }
+                            _ => '.',
+                        })
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
 
-                //Look Left
-                if location.1 > 0
-                    && self.map.data[location.0][location.1 - 1] == next_value
-                    && explored.insert((location.0, location.1 - 1))
-                {
-                    if !trail_end {
-                        queue.push_back((location.0, location.1 - 1));
-                    } else {
-                        end_of_trail_locations.insert((location.0, location.1 - 1));
+        ///Returns the locations of the trail ends reachable from this location according to the hiking rules.
+        fn reachable_summits(&self, head_location: (usize, usize)) -> HashSet<(usize, usize)> {
+            //We do Breadth-first search. See reasons why at the top of this file
+            let mut queue = VecDeque::from([head_location]);
+
+            //Using explored allows us to avoid visiting nodes repeatdly.
+            let mut explored = HashSet::from([head_location]);
+
+            while let Some(location) = queue.pop_front() {
+                for neighbor in self.next_steps(location) {
+                    if explored.insert(neighbor) {
+                        queue.push_back(neighbor);
                     }
                 }
             }
 
-            end_of_trail_locations.len()
+            explored.retain(|&location| self.is_trail_end(location));
+            explored
         }
 
         ///Returns the sum of all trailhead ratings in the map.
         /// A trailhead's rating is the number of *distinct* hiking trails that start with that trailhead.
-        pub fn rate_map(&self) -> usize {
-            //We rate each individual trailhead and sum their scores
+        ///Returns an error if there are more trails than fit in a usize.
+        pub fn rate_map(&self) -> Result<usize, &'static str> {
+            let ratings = self.ratings()?;
             self.trail_head_locations
                 .iter()
-                .map(|trailhead_location| self.rate_trailhead(*trailhead_location))
-                .sum()
+                .try_fold(0_usize, |sum, location| {
+                    sum.checked_add(ratings[location.0][location.1])
+                })
+                .ok_or(TOO_MANY_TRAILS)
         }
 
-        ///Returns the number of distinct hiking trails that start with at head_location
-        fn rate_trailhead(&self, head_location: (usize, usize)) -> usize {
-            //Note: As the hiking trail must increase by a value of 1 at each step,
-            //We can still do BFS but this time we use a HashMap instead of a HashSet for explored.
-            //The value is the multipler when we reach a 9.
-            //This way we still avoid visting the same node multiple times.
-
-            //If hiking trails didn't work like this it would be problematic
-            //for example
-            //02
-            //12
-            //Then we could go down left up and by the time we reach the top-right 2 it would have already gone on
-            //with an incorrect multiplier to other nodes.
-
-            let mut queue = VecDeque::new();
-
-            let mut explored: HashMap<(usize, usize), usize> = HashMap::new();
-
-            queue.push_back(head_location);
-
-            explored.insert(head_location, 1);
-
-            while let Some(location) = queue.pop_front() {
-                //see if one space up, down, left, or right from the location has a value greater by exactly one
-                //from the current location, if so enqueue it.
-                //If that value happens to be a 9 then just insert it to end_of_trail_locations instead.
-                let next_value = self.map.data[location.0][location.1] + 1;
-                let current_multiplier = *explored
-                    .get(&location)
-                    .expect("multipler should be initialized for locations queued");
-
-                if next_value == 10 {
-                    //We have reached a stage in the queue where the queue consists only of 9s
-                    //Note we have to re-add this 9 back to the queue first.
-                    queue.push_back(location);
-                    break;
-                }
+        ///Returns the rating and score of every location on the map (as if a trail could start there).
+        ///Returns an error if a rating doesn't fit in a usize.
+        pub fn trail_counts(&self) -> Result<TrailCounts, &'static str> {
+            Ok(TrailCounts {
+                ratings: self.ratings()?,
+                scores: self.scores(),
+            })
+        }
 
-                //Look up. Note this is safe as && short-circuits in Rust
-                //(the RHS is evaluated only if  location.0  > 0 is true).
-                //Using explored allows us to avoid visiting nodes repeatdly.
-                if location.0 > 0 && self.map.data[location.0 - 1][location.1] == next_value {
-                    //Update the multiplier
-                    explored
-                        .entry((location.0 - 1, location.1))
-                        .and_modify(|e| *e += current_multiplier)
-                        .or_insert(current_multiplier);
-
-                    //add the node to the queue if it was not there already
-                    if current_multiplier == *explored.get(&(location.0 - 1, location.1)).unwrap() {
-                        queue.push_back((location.0 - 1, location.1));
+        ///Returns the passable locations bucketed by height, in the order that goes from the end of the trails
+        ///back to the start
+        fn heights_from_trail_ends(&self) -> Vec<(usize, Vec<(usize, usize)>)> {
+            let mut by_height: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+            for (row_index, row) in self.map.data.iter().enumerate() {
+                for (col_index, height) in row.iter().enumerate() {
+                    if let Some(height) = height {
+                        by_height
+                            .entry(*height)
+                            .or_default()
+                            .push((row_index, col_index));
                     }
                 }
+            }
 
-                //Look down
-                if location.0 + 1 < self.map.row_num
-                    && self.map.data[location.0 + 1][location.1] == next_value
-                {
-                    explored
-                        .entry((location.0 + 1, location.1))
-                        .and_modify(|e| *e += current_multiplier)
-                        .or_insert(current_multiplier);
-
-                    //add the node to the queue if it was not there already
-                    if current_multiplier == *explored.get(&(location.0 + 1, location.1)).unwrap() {
-                        queue.push_back((location.0 + 1, location.1));
-                    }
-                }
+            if self.rules.ascending() {
+                by_height.into_iter().rev().collect()
+            } else {
+                by_height.into_iter().collect()
+            }
+        }
 
-                //Look Right
-                if location.1 + 1 < self.map.col_num
-                    && self.map.data[location.0][location.1 + 1] == next_value
-                {
-                    explored
-                        .entry((location.0, location.1 + 1))
-                        .and_modify(|e| *e += current_multiplier)
-                        .or_insert(current_multiplier);
-
-                    //add the node to the queue if it was not there already
-                    if current_multiplier == *explored.get(&(location.0, location.1 + 1)).unwrap() {
-                        queue.push_back((location.0, location.1 + 1));
-                    }
+        ///Returns the rating of every location on the map (as if a trail could start there).
+        ///
+        ///The map is a DAG (with an edge from each location to the locations a trail can go next), and a
+        ///location's rating is the sum of the ratings of where it can go next, so going over the locations
+        ///from the end of the trails back to the start we calculate all of them in a single pass
+        ///(rather than walking every trail separately).
+        ///The number of trails grows exponentially with their length, so we return an error if a rating
+        ///doesn't fit in a usize.
+        fn ratings(&self) -> Result<Vec<Vec<usize>>, &'static str> {
+            let mut ratings = vec![vec![0; self.map.col_num]; self.map.row_num];
+
+            for (_, locations) in self.heights_from_trail_ends() {
+                for location in locations {
+                    ratings[location.0][location.1] = if self.is_trail_end(location) {
+                        1
+                    } else {
+                        self.next_steps(location)
+                            .iter()
+                            .try_fold(0_usize, |sum, next| {
+                                sum.checked_add(ratings[next.0][next.1])
+                            })
+                            .ok_or(TOO_MANY_TRAILS)?
+                    };
                 }
+            }
 
-                //Look Left
-                if location.1 > 0 && self.map.data[location.0][location.1 - 1] == next_value {
-                    explored
-                        .entry((location.0, location.1 - 1))
-                        .and_modify(|e| *e += current_multiplier)
-                        .or_insert(current_multiplier);
+            Ok(ratings)
+        }
 
-                    //add the node to the queue if it was not there already
-                    if current_multiplier == *explored.get(&(location.0, location.1 - 1)).unwrap() {
-                        queue.push_back((location.0, location.1 - 1));
-                    }
+        ///Returns the score of every location on the map (as if a trail could start there).
+        ///
+        ///Same as ratings but with the sets of reachable trail ends instead of counts, and we only keep
+        ///the sets of the heights a trail can still go to.
+        fn scores(&self) -> Vec<Vec<usize>> {
+            let mut scores = vec![vec![0; self.map.col_num]; self.map.row_num];
+
+            let largest_step = self
+                .rules
+                .height_delta
+                .start()
+                .unsigned_abs()
+                .max(self.rules.height_delta.end().unsigned_abs());
+
+            //For each height we already did, the set of trail ends reachable from each location of that height
+            let mut summits: BTreeMap<usize, LocationSets> = BTreeMap::new();
+            for (height, locations) in self.heights_from_trail_ends() {
+                //A trail can't go from this height (or any we do after it) to a height this far away
+                summits.retain(|&done_height, _| done_height.abs_diff(height) <= largest_step);
+
+                let mut height_summits = HashMap::new();
+                for location in locations {
+                    let reachable: HashSet<(usize, usize)> = if self.is_trail_end(location) {
+                        HashSet::from([location])
+                    } else {
+                        self.next_steps(location)
+                            .iter()
+                            .flat_map(|next| {
+                                let next_height =
+                                    self.height(*next).expect("next steps are passable");
+                                &summits[&next_height][next]
+                            })
+                            .copied()
+                            .collect()
+                    };
+
+                    scores[location.0][location.1] = reachable.len();
+                    height_summits.insert(location, reachable);
                 }
+
+                summits.insert(height, height_summits);
             }
 
-            //calcuate the rating of the trailhead. Our queue now consists of all reachable 9's
-            //and we know how many times we reached each 9 due to the multiplier
-            queue
-                .into_iter()
-                .map(|location| {
-                    *explored
-                        .get(&location)
-                        .expect("multipler should be initialized for locations queued")
-                })
-                .sum()
+            scores
         }
     }
 }
 
 fn solution_part1(file_path: &str) -> usize {
-    let data: Vec<_> = fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|char| {
-                    char.to_digit(10)
-                        .expect("Map should be composed of digits 0-9") as usize
-                })
-                .collect::<Vec<usize>>()
-        })
-        .collect();
-
-    let map = topographic_map::TopMap::build(data);
+    let map = topographic_map::TopMap::parse(
+        &fs::read_to_string(file_path).expect("failed to open file"),
+        topographic_map::TrailRules::default(),
+    )
+    .unwrap();
     map.score_map()
 }
 
 fn solution_part2(file_path: &str) -> usize {
-    let data: Vec<_> = fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|char| {
-                    char.to_digit(10)
-                        .expect("Map should be composed of digits 0-9") as usize
-                })
-                .collect::<Vec<usize>>()
-        })
-        .collect();
-
-    let map = topographic_map::TopMap::build(data);
-    map.rate_map()
+    let map = topographic_map::TopMap::parse(
+        &fs::read_to_string(file_path).expect("failed to open file"),
+        topographic_map::TrailRules::default(),
+    )
+    .unwrap();
+    map.rate_map().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn answer() {
@@ -344,4 +515,180 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day10example.txt");
         assert_eq!(result, 81);
     }
+
+    const EXAMPLE: &str = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+
+    fn parse(input: &str) -> topographic_map::TopMap {
+        topographic_map::TopMap::parse(input, topographic_map::TrailRules::default()).unwrap()
+    }
+
+    #[test]
+    fn trail_enumeration() {
+        let map = parse(EXAMPLE);
+        let trailheads = map.trailheads();
+        assert_eq!(trailheads.len(), 9);
+
+        //the scores and ratings of the trailheads in the puzzle
+        let scores: Vec<usize> = trailheads.iter().map(|head| head.summits().len()).collect();
+        assert_eq!(scores, vec![5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        let ratings: Vec<usize> = trailheads
+            .iter()
+            .map(|head| head.trails(usize::MAX).count())
+            .collect();
+        assert_eq!(ratings, vec![20, 24, 10, 4, 1, 4, 5, 8, 5]);
+
+        //Every trail is a distinct path going up 1 at a time, ending at one of the summits
+        let head = &trailheads[0];
+        let trails: Vec<_> = head.trails(usize::MAX).collect();
+        for trail in &trails {
+            assert_eq!(trail.len(), 10);
+            assert!(head.summits().contains(trail.last().unwrap()));
+        }
+        assert_eq!(trails.iter().collect::<HashSet<_>>().len(), 20);
+        assert_eq!(head.trails(3).count(), 3);
+
+        let map = parse("0123\n1234\n8765\n9876");
+        let trail = map.trailheads()[0].trails(1).next().unwrap();
+        assert_eq!(map.render_trail(&trail), "0...\n1234\n...5\n9876");
+    }
+
+    #[test]
+    fn trail_counts() {
+        let map = parse(EXAMPLE);
+        assert_eq!(map.score_map(), 36);
+        assert_eq!(map.rate_map().unwrap(), 81);
+
+        //Every location agrees with enumerating the trails from it
+        let counts = map.trail_counts().unwrap();
+        for head in map.trailheads() {
+            let (row_index, col_index) = head.location;
+            assert_eq!(counts.scores[row_index][col_index], head.summits().len());
+            assert_eq!(
+                counts.ratings[row_index][col_index],
+                head.trails(usize::MAX).count()
+            );
+        }
+        //a 9 is a trail by itself
+        assert_eq!((counts.ratings[0][1], counts.scores[0][1]), (1, 1));
+
+        //A large map of diagonal stripes, where a trail is any way of going right and down 9 times
+        let size = 200;
+        let map = topographic_map::TopMap::build(
+            (0..size)
+                .map(|row_index| {
+                    (0..size)
+                        .map(|col_index| (row_index + col_index) % 10)
+                        .collect()
+                })
+                .collect(),
+        );
+        let counts = map.trail_counts().unwrap();
+        assert_eq!(counts.ratings[100][100], 1 << 9);
+        assert_eq!(counts.scores[100][100], 10);
+        //at the bottom right corner there is nowhere to go
+        assert_eq!(counts.ratings[size - 1][size - 1], 0);
+        assert!(map.rate_map().unwrap() > 1000 * (1 << 9));
+
+        //Without stripes every trail from the top left to the bottom right is 78 steps long,
+        //and there are 78 choose 39 (about 2.6 * 10^22) of them, which is too many to count in a usize
+        let size = 40;
+        let map = topographic_map::TopMap::build_with_rules(
+            (0..size)
+                .map(|row_index| {
+                    (0..size)
+                        .map(|col_index| Some(row_index + col_index))
+                        .collect()
+                })
+                .collect(),
+            topographic_map::TrailRules {
+                end_height: 78,
+                ..topographic_map::TrailRules::default()
+            },
+        )
+        .unwrap();
+        assert!(map.rate_map().is_err());
+        assert!(map.trail_counts().is_err());
+        assert_eq!(map.score_map(), 1);
+    }
+
+    #[test]
+    fn trail_rules() {
+        use topographic_map::{TopMap, TrailRules};
+
+        //The examples with impassable locations from the puzzle
+        assert_eq!(
+            parse("...0...\n...1...\n...2...\n6543456\n7.....7\n8.....8\n9.....9").score_map(),
+            2
+        );
+        assert_eq!(
+            parse("..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987....").score_map(),
+            4
+        );
+        assert_eq!(
+            parse(".....0.\n..4321.\n..5..2.\n..6543.\n..7..4.\n..8765.\n..9....")
+                .rate_map()
+                .unwrap(),
+            3
+        );
+
+        //Walking down from the 9's to the 0's gives the same trails backwards (and the sum of the scores is still
+        //how many pairs of a 0 and a 9 have a trail between them), even though each trailhead is different
+        let descent = TrailRules {
+            height_delta: -1..=-1,
+            start_height: 9,
+            end_height: 0,
+            ..TrailRules::default()
+        };
+        let map = TopMap::parse(EXAMPLE, descent).unwrap();
+        assert_eq!(map.rate_map().unwrap(), 81);
+        assert_eq!(map.score_map(), 36);
+        assert_eq!(map.trailheads().len(), 7);
+
+        //Going right is up 2 and going down is up 1, so with steps of 1 or 2 we can go down at any column
+        let steps = TrailRules {
+            height_delta: 1..=2,
+            ..TrailRules::default()
+        };
+        let map = TopMap::parse("02468\n13579", steps).unwrap();
+        assert_eq!((map.rate_map().unwrap(), map.score_map()), (5, 1));
+        assert_eq!(map.trailheads()[0].trails(usize::MAX).count(), 5);
+        assert_eq!(parse("02468\n13579").rate_map().unwrap(), 0);
+
+        let diagonal = TrailRules {
+            diagonal: true,
+            end_height: 2,
+            ..TrailRules::default()
+        };
+        assert_eq!(parse("0.\n.1").trailheads()[0].trails(10).count(), 0);
+        let map = TopMap::parse("0.2\n.1.\n2..", diagonal).unwrap();
+        assert_eq!((map.rate_map().unwrap(), map.score_map()), (2, 2));
+
+        let circles = TrailRules {
+            height_delta: -1..=1,
+            ..TrailRules::default()
+        };
+        assert!(TopMap::parse(EXAMPLE, circles).is_err());
+
+        //Heights above 9 can only be built, not parsed, and are rendered as '#'
+        let tall = TopMap::build_with_rules(
+            vec![vec![Some(8), Some(10), Some(12)]],
+            TrailRules {
+                height_delta: 2..=2,
+                start_height: 8,
+                end_height: 12,
+                ..TrailRules::default()
+            },
+        )
+        .unwrap();
+        let trails: Vec<_> = tall.trailheads()[0].trails(10).collect();
+        assert_eq!(trails, vec![vec![(0, 0), (0, 1), (0, 2)]]);
+        assert_eq!(tall.render_trail(&trails[0]), "8##");
+    }
 }
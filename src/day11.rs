@@ -1,98 +1,375 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fmt, fs, ops::AddAssign};
 
 //Part 1 solution notes:
 //use iterators to wrap stones. This will enable us to sometimes return 1 stone from processing a stone,
 //and sometimes return 2 stones.
 //Keep the stones numbers as chars (makes rule 1 and 2 easy).
 //Write a function that does 1 blink that takes and returns an iterator over stones.
+//Update: chars meant allocating a String per stone, numbers are just as easy:
+//the number of digits is ilog10 + 1, and splitting the digits in half is dividing by a power of 10.
 
 pub struct Stone {
-    pub data: String,
+    pub value: u64,
     pub multiplier: usize,
 }
 
-pub fn blink(stone_line: Box<dyn Iterator<Item = Stone>>) -> Box<dyn Iterator<Item = Stone>> {
-    //Note: each time we do stone_line.map(|stone| process_stone(stone)).flatten() we get a different new
-    //type that implements Iterator<Item=String>. That new type is the old type wrapped in FlatMap<..>
-    //and also captures the closure type of each map.
-    //Unfortunately, we cannot avoid trait objects here.
-    //Actually, I suppose we could write blink_num calls of blink by hand
-    //or via a macro: let stone_line = blink(stone_line) repeated blink num times
-    //and avoid trait objects that way but that is overkill for this puzzle.
+///The number on a stone got too big for a u64 (value is the number before it did)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Overflow {
+    pub value: u64,
+}
+
+///A rule for what happens to a stone when we blink: if applies is true for the number on a stone,
+///the stone is replaced by stones with the numbers transform returns (in order)
+pub struct Rule {
+    pub name: &'static str,
+    pub applies: Box<dyn Fn(u64) -> bool>,
+    pub transform: Box<dyn Fn(u64) -> Result<Vec<u64>, Overflow>>,
+}
+
+impl Rule {
+    pub fn new(
+        name: &'static str,
+        applies: impl Fn(u64) -> bool + 'static,
+        transform: impl Fn(u64) -> Result<Vec<u64>, Overflow> + 'static,
+    ) -> Rule {
+        Rule {
+            name,
+            applies: Box::new(applies),
+            transform: Box::new(transform),
+        }
+    }
+
+    ///A stone engraved with from is replaced by a stone engraved with to
+    pub fn replace(from: u64, to: u64) -> Rule {
+        Rule::new("replace", move |value| value == from, move |_| Ok(vec![to]))
+    }
+
+    ///A stone whose number of digits is a multiple of parts is split into parts stones,
+    ///each with an equal share of the digits (in order, and without leading 0's)
+    pub fn split_digits(parts: u32) -> Rule {
+        Rule::new(
+            "split digits",
+            move |value| digit_count(value).is_multiple_of(parts),
+            move |value| split_digits(value, parts),
+        )
+    }
+
+    ///Every stone is multiplied by factor
+    pub fn multiply(factor: u64) -> Rule {
+        Rule::new(
+            "multiply",
+            |_| true,
+            move |value| {
+                value
+                    .checked_mul(factor)
+                    .map(|value| vec![value])
+                    .ok_or(Overflow { value })
+            },
+        )
+    }
+}
+
+///An ordered list of rules. When we blink, the first rule that applies to a stone is the one that
+///changes it (and a stone no rule applies to stays the same).
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<Rule>) -> RuleSet {
+        RuleSet { rules }
+    }
+
+    ///The rules from the puzzle
+    pub fn puzzle() -> RuleSet {
+        RuleSet::new(vec![
+            Rule::replace(0, 1),
+            Rule::split_digits(2),
+            Rule::multiply(2024),
+        ])
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    ///Returns the numbers on the stones a stone engraved with value becomes
+    pub fn apply(&self, value: u64) -> Result<Vec<u64>, Overflow> {
+        match self.rules.iter().find(|rule| (rule.applies)(value)) {
+            Some(rule) => (rule.transform)(value),
+            None => Ok(vec![value]),
+        }
+    }
+}
+
+///Blinks once. Returns CountError::TooManyStones if a multiplier doesn't fit in a usize.
+pub fn blink(
+    stone_line: Box<dyn Iterator<Item = Stone>>,
+    rules: &RuleSet,
+) -> Result<Box<dyn Iterator<Item = Stone>>, CountError> {
+    //Note: the returned iterator is a Map<..> over the stones with a closure type we can't name in the signature,
+    //so we return a trait object (which also lets callers keep feeding the stones back into blink).
+    //We could avoid trait objects with a macro but that is overkill for this puzzle.
 
     //Part 2 optimization: we note we can reduce the problem.
     //at each stone_line we now eliminate repeated numbers (as they will result in the same number of stones anyway).
     //We keep track of a multiplier per stone.
-    let mut stones = HashMap::new();
+    let mut stones: HashMap<u64, usize> = HashMap::new();
 
-    for stone in stone_line.flat_map(process_stone) {
-        stones
-            .entry(stone.data)
-            .and_modify(|e| *e += stone.multiplier)
-            .or_insert(stone.multiplier);
+    for stone in stone_line {
+        //Every stone a stone becomes has its multiplier
+        for value in rules.apply(stone.value)? {
+            let multiplier = stones.entry(value).or_default();
+            *multiplier = multiplier
+                .checked_add(stone.multiplier)
+                .ok_or(CountError::TooManyStones)?;
+        }
     }
 
-    Box::new(stones.into_iter().map(|(key, value)| Stone {
-        data: key,
+    Ok(Box::new(stones.into_iter().map(|(key, value)| Stone {
+        value: key,
         multiplier: value,
-    }))
-}
-
-fn process_stone(mut stone: Stone) -> Vec<Stone> {
-    if stone.data == "0" {
-        stone.data.pop();
-        stone.data.push('1');
-        vec![stone]
-    } else if stone.data.len() % 2 == 0 {
-        //Note that stone.data.len gives us a result in bytes, not chars.
-        //But this is ok here as each digit 0-9 is 1 byte in the String as Rust Strings are UTF-8 encoded.
-
-        //We remove leading 0's from the right stone
-        let mut right_stone_data = stone
-            .data
-            .split_off(stone.data.len() / 2)
-            .trim_start_matches('0')
-            .to_string();
-        //It might be that the right stone was all 0's
-        if right_stone_data.is_empty() {
-            right_stone_data.push('0');
-        }
-        let right_stone = Stone {
-            data: right_stone_data,
-            multiplier: stone.multiplier,
-        };
-
-        vec![stone, right_stone]
-    } else {
-        stone.data = (stone
-            .data
-            .parse::<usize>()
-            .expect("Stone should be engraved with a number")
-            * 2024)
-            .to_string();
-        vec![stone]
+    })))
+}
+
+///Returns the number of digits of value (in base 10)
+fn digit_count(value: u64) -> u32 {
+    value.checked_ilog10().map_or(1, |log| log + 1)
+}
+
+///Splits the digits of value into parts numbers (parts must divide the number of digits of value).
+///The leading 0's of each part are taken care of by it being a number.
+///Returns Overflow if a power of 10 we divide by doesn't fit in a u64 (which can only happen for a single part).
+fn split_digits(value: u64, parts: u32) -> Result<Vec<u64>, Overflow> {
+    let part_digits = digit_count(value) / parts;
+    let overflow = Overflow { value };
+    let part_size = 10_u64.checked_pow(part_digits).ok_or(overflow)?;
+
+    (0..parts)
+        .rev()
+        .map(|part| {
+            let divisor = part_size.checked_pow(part).ok_or(overflow)?;
+            Ok(value / divisor % part_size)
+        })
+        .collect()
+}
+
+///The stones after a number of blinks
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlinkStats {
+    pub blinks: usize,
+    pub stone_count: usize,
+    ///How many different numbers are engraved on the stones
+    pub distinct_values: usize,
+}
+
+///An iterator over the BlinkStats after each blink (it never ends, unless a stone overflows
+///or there are too many stones to count)
+pub struct Blinks<'a> {
+    stones: Vec<Stone>,
+    rules: &'a RuleSet,
+    blinks: usize,
+    overflowed: bool,
+}
+
+impl Blinks<'_> {
+    fn blink(&mut self) -> Result<BlinkStats, CountError> {
+        let stone_line = Box::new(std::mem::take(&mut self.stones).into_iter());
+        self.stones = blink(stone_line, self.rules)?.collect();
+        self.blinks += 1;
+
+        //blink already merged the stones with the same number
+        let mut stone_count: usize = 0;
+        for stone in &self.stones {
+            stone_count = stone_count
+                .checked_add(stone.multiplier)
+                .ok_or(CountError::TooManyStones)?;
+        }
+
+        Ok(BlinkStats {
+            blinks: self.blinks,
+            stone_count,
+            distinct_values: self.stones.len(),
+        })
     }
 }
 
-///Returns the number of stones after blinking blink_num times
-fn solution(file_path: &str, blink_num: usize) -> usize {
-    //Note we MUST use collect here in the end so that stone_line owns the strings (i.e.
-    //we ensure map is consumed before passing stone_line to blink)
-    let stone_line: Vec<Stone> = fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .split(' ')
+impl Iterator for Blinks<'_> {
+    type Item = Result<BlinkStats, CountError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.overflowed {
+            return None;
+        }
+
+        let stats = self.blink();
+        self.overflowed = stats.is_err();
+        Some(stats)
+    }
+}
+
+pub fn blinks(stones: Vec<Stone>, rules: &RuleSet) -> Blinks<'_> {
+    Blinks {
+        stones,
+        rules,
+        blinks: 0,
+        overflowed: false,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CountError {
+    Overflow(Overflow),
+    ///There are more stones than fit in a usize (a StoneCounter can still count them)
+    TooManyStones,
+}
+
+impl From<Overflow> for CountError {
+    fn from(overflow: Overflow) -> CountError {
+        CountError::Overflow(overflow)
+    }
+}
+
+///A count that can get as big as it needs to (the number of stones grows exponentially with the blinks)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct StoneCount {
+    //The digits in base 2^64, least significant first (and without 0's at the end)
+    limbs: Vec<u64>,
+}
+
+impl From<u64> for StoneCount {
+    fn from(count: u64) -> StoneCount {
+        let mut limbs = vec![count];
+        limbs.retain(|&limb| limb != 0);
+        StoneCount { limbs }
+    }
+}
+
+impl AddAssign<&StoneCount> for StoneCount {
+    fn add_assign(&mut self, other: &StoneCount) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+
+        let mut carry = false;
+        for (index, limb) in self.limbs.iter_mut().enumerate() {
+            if index >= other.limbs.len() && !carry {
+                break;
+            }
+            let other_limb = other.limbs.get(index).copied().unwrap_or(0);
+            let (sum, overflowed) = limb.overflowing_add(other_limb);
+            let (sum, carried) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = overflowed || carried;
+        }
+        if carry {
+            self.limbs.push(1);
+        }
+    }
+}
+
+impl fmt::Display for StoneCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //We repeatedly divide by 10^19 (the largest power of 10 that fits in a u64) to get the decimal digits
+        //19 at a time, least significant first
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut chunks: Vec<u64> = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder: u128 = 0;
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 64) | *limb as u128;
+                *limb = (current / CHUNK) as u64;
+                remainder = current % CHUNK;
+            }
+            chunks.push(remainder as u64);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                write!(f, "{most_significant}")?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{chunk:019}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+///Counts how many stones a stone becomes after some blinks, remembering the count for every
+///(value, remaining blinks) it sees along the way. So asking again (for more blinks, or other stones)
+///reuses everything counted before.
+pub struct StoneCounter<'a> {
+    rules: &'a RuleSet,
+    memo: HashMap<(u64, usize), StoneCount>,
+}
+
+impl<'a> StoneCounter<'a> {
+    pub fn new(rules: &'a RuleSet) -> StoneCounter<'a> {
+        StoneCounter {
+            rules,
+            memo: HashMap::new(),
+        }
+    }
+
+    ///Returns how many stones a stone engraved with value becomes after blink_num blinks
+    pub fn count(&mut self, value: u64, blink_num: usize) -> Result<StoneCount, Overflow> {
+        if blink_num == 0 {
+            return Ok(StoneCount::from(1));
+        }
+        if let Some(count) = self.memo.get(&(value, blink_num)) {
+            return Ok(count.clone());
+        }
+
+        let mut count = StoneCount::default();
+        for next_value in self.rules.apply(value)? {
+            count += &self.count(next_value, blink_num - 1)?;
+        }
+
+        self.memo.insert((value, blink_num), count.clone());
+        Ok(count)
+    }
+
+    ///Returns how many stones the stones engraved with values become after blink_num blinks
+    pub fn count_line(&mut self, values: &[u64], blink_num: usize) -> Result<StoneCount, Overflow> {
+        let mut count = StoneCount::default();
+        for &value in values {
+            count += &self.count(value, blink_num)?;
+        }
+        Ok(count)
+    }
+}
+
+///Parses the numbers engraved on the stones (separated by whitespace)
+fn parse(input: &str) -> Vec<Stone> {
+    input
+        .split_whitespace()
         .map(|word| Stone {
-            data: word.to_string(),
+            value: word
+                .parse()
+                .expect("Stone should be engraved with a number"),
             multiplier: 1,
         })
-        .collect::<Vec<Stone>>();
+        .collect()
+}
+
+///Returns the number of stones after blinking blink_num times
+fn solution(file_path: &str, blink_num: usize) -> usize {
+    let stone_line = parse(&fs::read_to_string(file_path).expect("failed to open file"));
+    let rules = RuleSet::puzzle();
 
     let mut stone_line: Box<dyn Iterator<Item = Stone>> = Box::new(stone_line.into_iter());
     //Trait objects are needed as the explicit type of stone_line changes with each call to blink
     //(it is wrapped inside Flatten<Map<..>> and also captures the closure type of each map).
     //We could avoid this with a macro if performance was critical.
     for _ in 0..blink_num {
-        stone_line = blink(stone_line);
+        stone_line = blink(stone_line, &rules).expect("Stones should be countable");
     }
 
     //We remember to take the multiplier into account
@@ -115,4 +392,166 @@ mod tests {
         let result = solution("puzzle_inputs/day11example.txt", 25);
         assert_eq!(result, 55312);
     }
+
+    #[test]
+    fn integer_stones() {
+        assert_eq!(
+            [0, 9, 10, 99, 1000, 253000].map(digit_count),
+            [1, 1, 2, 2, 4, 6]
+        );
+
+        let mut stone_line: Box<dyn Iterator<Item = Stone>> = Box::new(parse("125 17").into_iter());
+        for expected in [3, 4, 5, 9, 13, 22] {
+            stone_line = blink(stone_line, &RuleSet::puzzle()).unwrap();
+            let stones: Vec<Stone> = stone_line.collect();
+            assert_eq!(
+                stones.iter().map(|stone| stone.multiplier).sum::<usize>(),
+                expected
+            );
+            stone_line = Box::new(stones.into_iter());
+        }
+
+        //Splitting drops the leading 0's of the right half
+        assert_eq!(RuleSet::puzzle().apply(1000), Ok(vec![10, 0]));
+        assert_eq!(split_digits(120034, 3), Ok(vec![12, 0, 34]));
+        //A single part of a 20 digit number is bigger than any power of 10 that fits in a u64
+        assert_eq!(
+            RuleSet::new(vec![Rule::split_digits(1)]).apply(u64::MAX),
+            Err(Overflow { value: u64::MAX })
+        );
+
+        //A 19 digit number times 2024 doesn't fit in a u64
+        let huge = Box::new(parse("1000000000000000000 5").into_iter());
+        assert_eq!(
+            blink(huge, &RuleSet::puzzle()).err(),
+            Some(CountError::Overflow(Overflow {
+                value: 1000000000000000000
+            }))
+        );
+    }
+
+    #[test]
+    fn custom_rule_sets() {
+        ///Returns the values (and multipliers) after each blink, starting from a single stone
+        fn blinks(rules: &RuleSet, value: u64, blink_num: usize) -> Vec<Vec<(u64, usize)>> {
+            let mut stone_line: Box<dyn Iterator<Item = Stone>> = Box::new(
+                vec![Stone {
+                    value,
+                    multiplier: 1,
+                }]
+                .into_iter(),
+            );
+            let mut history = Vec::new();
+            for _ in 0..blink_num {
+                let mut stones: Vec<(u64, usize)> = blink(stone_line, rules)
+                    .unwrap()
+                    .map(|stone| (stone.value, stone.multiplier))
+                    .collect();
+                stones.sort();
+                stone_line = Box::new(
+                    stones
+                        .clone()
+                        .into_iter()
+                        .map(|(value, multiplier)| Stone { value, multiplier }),
+                );
+                history.push(stones);
+            }
+            history
+        }
+
+        //Split into three, otherwise multiply by 3
+        let rules = RuleSet::new(vec![
+            Rule::replace(0, 1),
+            Rule::split_digits(3),
+            Rule::multiply(3),
+        ]);
+        assert_eq!(
+            blinks(&rules, 123456, 3),
+            vec![
+                vec![(12, 1), (34, 1), (56, 1)],
+                vec![(36, 1), (102, 1), (168, 1)],
+                //the two 1's are a single stone with a multiplier of 2
+                vec![(0, 1), (1, 2), (2, 1), (6, 1), (8, 1), (108, 1)],
+            ]
+        );
+
+        //Large numbers become their digit sum, and a 7 stays the same
+        let rules = RuleSet::new(vec![
+            Rule::new(
+                "digit sum",
+                |value| digit_count(value) > 4,
+                |value| {
+                    Ok(vec![
+                        split_digits(value, digit_count(value))?.into_iter().sum(),
+                    ])
+                },
+            ),
+            Rule::new("lucky", |value| value == 7, |value| Ok(vec![value])),
+            Rule::multiply(2024),
+        ]);
+        let history: Vec<u64> = blinks(&rules, 5, 5)
+            .into_iter()
+            .map(|stones| stones[0].0)
+            .collect();
+        assert_eq!(history, vec![10120, 4, 8096, 16386304, 31]);
+        assert_eq!(blinks(&rules, 7, 2), vec![vec![(7, 1)], vec![(7, 1)]]);
+        assert_eq!(rules.rules()[0].name, "digit sum");
+    }
+
+    #[test]
+    fn blink_stats_and_memoized_counts() {
+        let rules = RuleSet::puzzle();
+
+        //The example from the puzzle, blink by blink
+        let stats: Vec<BlinkStats> = blinks(parse("125 17"), &rules)
+            .take(6)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|stats| (stats.stone_count, stats.distinct_values))
+                .collect::<Vec<_>>(),
+            vec![(3, 3), (4, 4), (5, 5), (9, 8), (13, 12), (22, 15)]
+        );
+        assert_eq!(stats[5].blinks, 6);
+
+        let stats = blinks(parse("125 17"), &rules).nth(24).unwrap().unwrap();
+        assert_eq!(stats.stone_count, 55312);
+
+        let huge = parse("1000000000000000000");
+        assert_eq!(
+            blinks(huge, &rules).collect::<Vec<_>>(),
+            vec![Err(CountError::Overflow(Overflow {
+                value: 1000000000000000000
+            }))]
+        );
+        //After 106 blinks there are more stones than fit in a usize
+        let stats: Vec<_> = blinks(parse("125 17"), &rules).collect();
+        assert_eq!(stats.len(), 106);
+        assert_eq!(stats[105], Err(CountError::TooManyStones));
+
+        //The counter agrees with blinking, and keeps what it counted between queries
+        let mut counter = StoneCounter::new(&rules);
+        let count = |count: u64| Ok(StoneCount::from(count));
+        assert_eq!(counter.count_line(&[125, 17], 25), count(55312));
+        let remembered = counter.memo.len();
+        assert_eq!(counter.count_line(&[125, 17], 25), count(55312));
+        assert_eq!(counter.memo.len(), remembered);
+        assert_eq!(counter.count_line(&[125, 17], 6), count(22));
+        assert_eq!(counter.count_line(&[125, 17], 75), count(65601038650482));
+        assert_eq!(counter.count_line(&[125, 17], 0), count(2));
+        //The number of stones grows exponentially, after 1000 blinks there are 182 digits of them
+        assert_eq!(
+            counter.count_line(&[125, 17], 1000).unwrap().to_string(),
+            "54741524973376212565260478487051315295505339617677525803553712687264067878870028349847968645126611553215608193006204454954030148664889783971438989158943080990643210162103397674552924"
+        );
+
+        let flip = RuleSet::new(vec![Rule::replace(0, 1), Rule::replace(1, 0)]);
+        assert_eq!(
+            StoneCounter::new(&flip).count_line(&[0, 1, 7], 1000),
+            count(3)
+        );
+        assert_eq!(StoneCount::default().to_string(), "0");
+    }
 }
@@ -1,56 +1,154 @@
+use std::collections::VecDeque;
 use std::fs;
+use std::io::{self, Read};
 
-use regex::Regex;
+use regex::bytes::Regex;
 
-///Reads the input text and returns the sum of all mul(X,Y) operations
-fn solution_part1() -> i32 {
-    let raw_input = fs::read_to_string("puzzle_inputs/day3.txt").expect("failed to open file");
-
-    //We use a Regex, the following website was helpful https://rustexp.lpil.uk/
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-    re.captures_iter(&raw_input)
-        .map(|c| {
-            let (_, [raw_x, raw_y]) = c.extract();
-            let (x, y) = (raw_x.parse::<i32>().unwrap(), raw_y.parse::<i32>().unwrap());
-            x * y
-        })
-        .sum()
+///The longest instruction we scan for is mul(XXX,YYY) which is 12 bytes long
+const MAX_INSTRUCTION_LEN: usize = 12;
+
+///The chunk size used when reading the puzzle input
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+///An instruction found in the corrupted memory
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction {
+    Mul(i32, i32),
+    Do,
+    Dont,
 }
 
-///Reads the input text and returns the sum of all mul(X,Y) operations with do() and don't operations enabled
-fn solution_part2() -> i32 {
-    let raw_input = fs::read_to_string("puzzle_inputs/day3.txt").expect("failed to open file");
+///Scans a reader for instructions, reading it in fixed-size chunks.
+///
+///Only the current chunk (plus a small tail of at most MAX_INSTRUCTION_LEN - 1 bytes carried over
+///from the previous chunk) is ever held in memory, so an instruction like mul(12,3 that is split
+///across a chunk boundary is still found.
+pub struct InstructionScanner<R: Read> {
+    reader: R,
+    chunk_size: usize,
+    re: Regex,
+    //Bytes we have read but not yet fully scanned
+    buffer: Vec<u8>,
+    //Instructions we have found but not yet yielded
+    pending: VecDeque<Instruction>,
+    reader_exhausted: bool,
+}
+
+impl<R: Read> InstructionScanner<R> {
+    pub fn new(reader: R, chunk_size: usize) -> InstructionScanner<R> {
+        if chunk_size == 0 {
+            panic!("chunk size must be positive")
+        }
+
+        //We use a Regex, the following website was helpful https://rustexp.lpil.uk/
+        //Note we use a bytes Regex since a chunk boundary might split a multi-byte UTF-8 char
+        let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|do\(\)|don't\(\)").unwrap();
 
+        InstructionScanner {
+            reader,
+            chunk_size,
+            re,
+            buffer: Vec::with_capacity(chunk_size + MAX_INSTRUCTION_LEN),
+            pending: VecDeque::new(),
+            reader_exhausted: false,
+        }
+    }
+
+    ///Read the next chunk and scan it (together with the carried over tail) for instructions.
+    fn scan_next_chunk(&mut self) {
+        let old_len = self.buffer.len();
+        self.buffer.resize(old_len + self.chunk_size, 0);
+        //An interrupted read didn't read anything so we just try again (like read_exact does)
+        let bytes_read = loop {
+            match self.reader.read(&mut self.buffer[old_len..]) {
+                Ok(bytes_read) => break bytes_read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => panic!("failed to read input: {error}"),
+            }
+        };
+        self.buffer.truncate(old_len + bytes_read);
+
+        if bytes_read == 0 {
+            self.reader_exhausted = true;
+        }
+
+        let mut scanned_up_to = 0;
+        for capture in self.re.captures_iter(&self.buffer) {
+            //Note capture.get(0) is guaranteed to be Some(_)
+            let whole = capture.get(0).unwrap();
+            scanned_up_to = whole.end();
+
+            let instruction = match whole.as_bytes() {
+                b"do()" => Instruction::Do,
+                b"don't()" => Instruction::Dont,
+                //Note this occurs only when the insturction is mul(X,Y)
+                _ => {
+                    //The captures are ASCII digits so these conversions can't fail
+                    let parse = |index: usize| {
+                        std::str::from_utf8(capture.get(index).unwrap().as_bytes())
+                            .unwrap()
+                            .parse::<i32>()
+                            .unwrap()
+                    };
+                    Instruction::Mul(parse(1), parse(2))
+                }
+            };
+            self.pending.push_back(instruction);
+        }
+
+        //Any instruction that is cut by the end of the buffer starts within its last
+        //MAX_INSTRUCTION_LEN - 1 bytes, so that is all we need to carry over to the next chunk
+        let keep_from =
+            scanned_up_to.max(self.buffer.len().saturating_sub(MAX_INSTRUCTION_LEN - 1));
+        self.buffer.drain(..keep_from);
+    }
+}
+
+impl<R: Read> Iterator for InstructionScanner<R> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Instruction> {
+        while self.pending.is_empty() && !self.reader_exhausted {
+            self.scan_next_chunk();
+        }
+        self.pending.pop_front()
+    }
+}
+
+///Returns the sum of all mul(X,Y) operations in the reader.
+///If conditionals is true then do() and don't() operations are enabled.
+pub fn sum_muls<R: Read>(reader: R, chunk_size: usize, conditionals: bool) -> i32 {
     let mut sum = 0;
     let mut mul_enabled = true;
-    //We use a Regex, the following website was helpful https://rustexp.lpil.uk/
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|do\(\)|don't\(\)").unwrap();
-    for capture in re.captures_iter(&raw_input) {
-        //Note capture.get(0) is guaranteed to be Some(_)
-        let instruction = capture.get(0).unwrap().as_str();
-
-        if instruction == "do()" {
-            mul_enabled = true;
-        } else if instruction == "don't()" {
-            mul_enabled = false;
-        } else if mul_enabled
-        //Note this else occurs only when the insturction is mul(X,Y)
-        {
-            let (raw_x, raw_y) = (
-                capture.get(1).unwrap().as_str(),
-                capture.get(2).unwrap().as_str(),
-            );
-            let (x, y) = (raw_x.parse::<i32>().unwrap(), raw_y.parse::<i32>().unwrap());
-            sum += x * y;
+
+    for instruction in InstructionScanner::new(reader, chunk_size) {
+        match instruction {
+            Instruction::Do => mul_enabled = true,
+            Instruction::Dont => mul_enabled = !conditionals,
+            Instruction::Mul(x, y) if mul_enabled => sum += x * y,
+            Instruction::Mul(_, _) => {}
         }
     }
     sum
 }
 
+///Reads the input text and returns the sum of all mul(X,Y) operations
+fn solution_part1() -> i32 {
+    let file = fs::File::open("puzzle_inputs/day3.txt").expect("failed to open file");
+    sum_muls(file, DEFAULT_CHUNK_SIZE, false)
+}
+
+///Reads the input text and returns the sum of all mul(X,Y) operations with do() and don't operations enabled
+fn solution_part2() -> i32 {
+    let file = fs::File::open("puzzle_inputs/day3.txt").expect("failed to open file");
+    sum_muls(file, DEFAULT_CHUNK_SIZE, true)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use regex::Regex;
 
     #[test]
     fn answer() {
@@ -105,4 +203,40 @@ mod tests {
 
         assert_eq!(sum, 48);
     }
+
+    #[test]
+    fn streaming_any_chunk_size() {
+        let input_part1 = "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+        let input_part2 =
+            "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+        //Every chunk size splits some instruction across a chunk boundary
+        for chunk_size in 1..=input_part1.len() + 1 {
+            assert_eq!(sum_muls(input_part1.as_bytes(), chunk_size, false), 161);
+            assert_eq!(sum_muls(input_part2.as_bytes(), chunk_size, true), 48);
+        }
+
+        let instructions: Vec<_> =
+            InstructionScanner::new("mul(12,3\nmul(12,34)".as_bytes(), 7).collect();
+        assert_eq!(instructions, vec![Instruction::Mul(12, 34)]);
+        //A reader that is interrupted before every read that succeeds
+        struct Interrupting<'a> {
+            data: &'a [u8],
+            interrupt: bool,
+        }
+        impl Read for Interrupting<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.interrupt = !self.interrupt;
+                if self.interrupt {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                self.data.read(buf)
+            }
+        }
+        let reader = Interrupting {
+            data: input_part2.as_bytes(),
+            interrupt: false,
+        };
+        assert_eq!(sum_muls(reader, 5, true), 48);
+    }
 }
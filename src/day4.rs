@@ -1,5 +1,193 @@
 use std::fs;
 
+///One of the 8 directions a word can be read in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    ///All 8 directions (horizontal, vertical, diagonal, written backwards)
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::UpRight,
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+    ];
+
+    ///Only the 4 horizontal and vertical directions
+    pub const STRAIGHT: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    ///Only the 4 diagonal directions
+    pub const DIAGONAL: [Direction; 4] = [
+        Direction::UpRight,
+        Direction::DownRight,
+        Direction::DownLeft,
+        Direction::UpLeft,
+    ];
+
+    ///The (row, col) change of taking 1 step in this direction
+    fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::UpRight => (-1, 1),
+            Direction::Right => (0, 1),
+            Direction::DownRight => (1, 1),
+            Direction::Down => (1, 0),
+            Direction::DownLeft => (1, -1),
+            Direction::Left => (0, -1),
+            Direction::UpLeft => (-1, -1),
+        }
+    }
+}
+
+///How a template is turned before being matched against the grid:
+///first mirrored left to right (if mirrored is true), then rotated clockwise quarter_turns times.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Orientation {
+    pub quarter_turns: u8,
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    ///The template as given
+    pub const IDENTITY: Orientation = Orientation {
+        quarter_turns: 0,
+        mirrored: false,
+    };
+
+    ///The 4 rotations of the template
+    pub const ROTATIONS: [Orientation; 4] = [
+        Orientation::IDENTITY,
+        Orientation {
+            quarter_turns: 1,
+            mirrored: false,
+        },
+        Orientation {
+            quarter_turns: 2,
+            mirrored: false,
+        },
+        Orientation {
+            quarter_turns: 3,
+            mirrored: false,
+        },
+    ];
+
+    ///The 4 rotations and the 4 mirrored rotations of the template
+    pub const ALL: [Orientation; 8] = [
+        Orientation::ROTATIONS[0],
+        Orientation::ROTATIONS[1],
+        Orientation::ROTATIONS[2],
+        Orientation::ROTATIONS[3],
+        Orientation {
+            quarter_turns: 0,
+            mirrored: true,
+        },
+        Orientation {
+            quarter_turns: 1,
+            mirrored: true,
+        },
+        Orientation {
+            quarter_turns: 2,
+            mirrored: true,
+        },
+        Orientation {
+            quarter_turns: 3,
+            mirrored: true,
+        },
+    ];
+}
+
+///A rectangular 2-D pattern to search for. None cells are wildcards that match any char.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Template {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+impl Template {
+    ///Parse a template from lines of text where wildcard marks a cell that matches any char.
+    ///For example the X shaped MAS is "M.S\n.A.\nM.S" with '.' as the wildcard.
+    pub fn parse(pattern: &str, wildcard: char) -> Template {
+        let cells: Vec<Vec<Option<char>>> = pattern
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|char| if char == wildcard { None } else { Some(char) })
+                    .collect()
+            })
+            .collect();
+
+        if cells.is_empty() || cells[0].is_empty() {
+            panic!("empty template")
+        }
+        if cells.iter().any(|row| row.len() != cells[0].len()) {
+            panic!("template is not rectangular")
+        }
+
+        Template { cells }
+    }
+
+    ///Return this template turned according to orientation
+    fn oriented(&self, orientation: Orientation) -> Template {
+        let mut cells = self.cells.clone();
+        if orientation.mirrored {
+            for row in cells.iter_mut() {
+                row.reverse();
+            }
+        }
+
+        for _ in 0..orientation.quarter_turns % 4 {
+            //rotate 90 degrees clockwise: the first column (read bottom to top) becomes the first row
+            let row_num = cells.len();
+            let col_num = cells[0].len();
+            cells = (0..col_num)
+                .map(|col_index| {
+                    (0..row_num)
+                        .rev()
+                        .map(|row_index| cells[row_index][col_index])
+                        .collect()
+                })
+                .collect();
+        }
+
+        Template { cells }
+    }
+}
+
+///A word found in the grid: the coordinate of its first letter, the direction it is read in
+///and the cells it covers (in reading order)
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct WordMatch {
+    pub start: (usize, usize),
+    pub direction: Direction,
+    pub cells: Vec<(usize, usize)>,
+}
+
+///A template found in the grid: the coordinate of the top left corner of the (turned) template
+///and the orientation it was turned to, along with the cells it covers (wildcard cells are not included)
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct TemplateMatch {
+    pub start: (usize, usize),
+    pub orientation: Orientation,
+    pub cells: Vec<(usize, usize)>,
+}
+
 ///A Matrix holds a vector of vectors of chars
 pub struct Matrix {
     data: Vec<Vec<char>>,
@@ -24,189 +212,231 @@ impl Matrix {
         }
     }
 
-    ///Find number of times XMAS appears where the X appears in current row and col index (looks in all directions)
-    pub fn find_xmas(&self, row_index: usize, col_index: usize) -> usize {
-        let mut potential_match: Vec<[char; 3]> = Vec::with_capacity(8);
-
-        //we use slight optimizations here (no point looking right when doing so goes out of the data bounds)
-        let should_look_right = col_index + 3 < self.col_num;
-        let should_look_down = row_index + 3 < self.row_num;
-        let should_look_left = col_index >= 3; //added 3 to both sides to avoid casting col_index to i32
-        let should_look_up = row_index >= 3;
-
-        //Another slight optimization we do is only check for MAS since we already know there is an X
-        //at row_index, col_index
-
-        if should_look_right {
-            //look right
-            potential_match.push(
-                self.data[row_index][col_index + 1..=col_index + 3]
-                    .try_into()
-                    .expect("failed conversion"),
-            );
-
-            if should_look_up {
-                //look up-right diagonal
-                let value = [
-                    self.data[row_index - 1][col_index + 1],
-                    self.data[row_index - 2][col_index + 2],
-                    self.data[row_index - 3][col_index + 3],
-                ];
-                potential_match.push(value);
-            }
+    ///Returns the location steps steps away from location in direction, or None if that is out of bounds
+    fn step(
+        &self,
+        location: (usize, usize),
+        direction: Direction,
+        steps: usize,
+    ) -> Option<(usize, usize)> {
+        let (row_delta, col_delta) = direction.delta();
+        let row_index = location
+            .0
+            .checked_add_signed(row_delta * steps as isize)
+            .filter(|&row_index| row_index < self.row_num)?;
+        let col_index = location
+            .1
+            .checked_add_signed(col_delta * steps as isize)
+            .filter(|&col_index| col_index < self.col_num)?;
+        Some((row_index, col_index))
+    }
 
-            if should_look_down {
-                //look down-right diagonal
-                let value = [
-                    self.data[row_index + 1][col_index + 1],
-                    self.data[row_index + 2][col_index + 2],
-                    self.data[row_index + 3][col_index + 3],
-                ];
-                potential_match.push(value);
-            }
+    ///Find every occurence of word that starts at the given location, reading in any of the given directions
+    pub fn find_word_at(
+        &self,
+        location: (usize, usize),
+        word: &str,
+        directions: &[Direction],
+    ) -> Vec<WordMatch> {
+        if word.is_empty() {
+            panic!("empty word")
         }
 
-        if should_look_left {
-            //look left
-            //silcing here we give us potentially "SAM" when we really want it to be "MAS"
-            let value = [
-                self.data[row_index][col_index - 1],
-                self.data[row_index][col_index - 2],
-                self.data[row_index][col_index - 3],
-            ];
-            potential_match.push(value);
-
-            if should_look_up {
-                //look up-left diagonal
-                let value = [
-                    self.data[row_index - 1][col_index - 1],
-                    self.data[row_index - 2][col_index - 2],
-                    self.data[row_index - 3][col_index - 3],
-                ];
-                potential_match.push(value);
+        directions
+            .iter()
+            .filter_map(|&direction| {
+                //Collect the cells the word covers, bailing out on the first mismatch
+                let cells =
+                    word.chars()
+                        .enumerate()
+                        .map(|(index, letter)| {
+                            self.step(location, direction, index).filter(
+                                |&(row_index, col_index)| self.data[row_index][col_index] == letter,
+                            )
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+
+                Some(WordMatch {
+                    start: location,
+                    direction,
+                    cells,
+                })
+            })
+            .collect()
+    }
+
+    ///Find every occurence of word in the grid, reading in any of the given directions
+    pub fn find_word(&self, word: &str, directions: &[Direction]) -> Vec<WordMatch> {
+        let mut matches = Vec::new();
+        for row_index in 0..self.row_num {
+            for col_index in 0..self.col_num {
+                matches.extend(self.find_word_at((row_index, col_index), word, directions));
             }
-            if should_look_down {
-                //look down-left diagonal
-                let value = [
-                    self.data[row_index + 1][col_index - 1],
-                    self.data[row_index + 2][col_index - 2],
-                    self.data[row_index + 3][col_index - 3],
-                ];
-                potential_match.push(value);
+        }
+        matches
+    }
+
+    ///Check if template (already turned) fits in the grid with its top left corner at location.
+    ///Returns the cells covered by the non-wildcard part of the template if it does and None otherwise.
+    fn template_fits_at(
+        &self,
+        location: (usize, usize),
+        template: &Template,
+    ) -> Option<Vec<(usize, usize)>> {
+        let (row_index, col_index) = location;
+        if row_index + template.cells.len() > self.row_num
+            || col_index + template.cells[0].len() > self.col_num
+        {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        for (row_offset, row) in template.cells.iter().enumerate() {
+            for (col_offset, cell) in row.iter().enumerate() {
+                if let Some(char) = cell {
+                    let cell_location = (row_index + row_offset, col_index + col_offset);
+                    if self.data[cell_location.0][cell_location.1] != *char {
+                        return None;
+                    }
+                    cells.push(cell_location);
+                }
             }
         }
+        Some(cells)
+    }
 
-        if should_look_up {
-            //look up
-            let value = [
-                self.data[row_index - 1][col_index],
-                self.data[row_index - 2][col_index],
-                self.data[row_index - 3][col_index],
-            ];
-            potential_match.push(value);
-        }
-        if should_look_down {
-            //look down
-            let value = [
-                self.data[row_index + 1][col_index],
-                self.data[row_index + 2][col_index],
-                self.data[row_index + 3][col_index],
-            ];
-            potential_match.push(value);
-        }
-
-        //count the matches
-        let mut counter = 0;
-        for entry in potential_match {
-            if entry == ['M', 'A', 'S'] {
-                counter += 1;
+    ///Turn template by each of the given orientations, skipping orientations
+    ///that give the same pattern as an earlier one (so a symmetric template isn't matched twice)
+    fn distinct_orientations(
+        template: &Template,
+        orientations: &[Orientation],
+    ) -> Vec<(Orientation, Template)> {
+        let mut turned: Vec<(Orientation, Template)> = Vec::with_capacity(orientations.len());
+        for &orientation in orientations {
+            let candidate = template.oriented(orientation);
+            if turned.iter().all(|(_, seen)| *seen != candidate) {
+                turned.push((orientation, candidate));
             }
         }
+        turned
+    }
 
-        counter
+    ///Find every occurence of template with its top left corner at location, in any of the given orientations
+    pub fn find_template_at(
+        &self,
+        location: (usize, usize),
+        template: &Template,
+        orientations: &[Orientation],
+    ) -> Vec<TemplateMatch> {
+        Matrix::distinct_orientations(template, orientations)
+            .into_iter()
+            .filter_map(|(orientation, turned)| {
+                let cells = self.template_fits_at(location, &turned)?;
+                Some(TemplateMatch {
+                    start: location,
+                    orientation,
+                    cells,
+                })
+            })
+            .collect()
     }
 
-    ///Find if there is an X shaped MAS centered at this point, returning 1 if there is and 0 otherwise
-    pub fn find_x_shaped_mas(&self, row_index: usize, col_index: usize) -> usize {
-        //we use slight optimizations here (no point looking right when doing so goes out of the data bounds)
-        let should_look_right = col_index + 1 < self.col_num;
-        let should_look_down = row_index + 1 < self.row_num;
-        let should_look_left = col_index >= 1;
-        let should_look_up = row_index >= 1;
-
-        //Another slight optimization we do is only check for M and S since we already know there is an A
-        //at row_index, col_index
-
-        //We must be able to to look in all directions if there is an X shaped M A S at this point
-        if should_look_right && should_look_down && should_look_left && should_look_up {
-            let top_left = self.data[row_index - 1][col_index - 1];
-            let bot_right = self.data[row_index + 1][col_index + 1];
-            if (top_left == 'M' && bot_right == 'S') || (top_left == 'S' && bot_right == 'M') {
-                //the left diagonal is good, we now check the right diagonal
-
-                let top_right = self.data[row_index - 1][col_index + 1];
-                let bot_left = self.data[row_index + 1][col_index - 1];
-                if (top_right == 'M' && bot_left == 'S') || (top_right == 'S' && bot_left == 'M') {
-                    //both diagonals good, found an X shaped MAS here
-                    return 1;
+    ///Find every occurence of template in the grid, in any of the given orientations
+    pub fn find_template(
+        &self,
+        template: &Template,
+        orientations: &[Orientation],
+    ) -> Vec<TemplateMatch> {
+        let turned = Matrix::distinct_orientations(template, orientations);
+
+        let mut matches = Vec::new();
+        for row_index in 0..self.row_num {
+            for col_index in 0..self.col_num {
+                for (orientation, template) in turned.iter() {
+                    if let Some(cells) = self.template_fits_at((row_index, col_index), template) {
+                        matches.push(TemplateMatch {
+                            start: (row_index, col_index),
+                            orientation: *orientation,
+                            cells,
+                        });
+                    }
                 }
             }
         }
-
-        //if we got here then there is no X-shaped MAS here
-        0
+        matches
     }
-}
 
-///Reads the input text and returns the number of occurences of XMAS (Note we are guaranteed the input is rectanguler)
-fn solution_part1(file_path: &str) -> usize {
-    let data: Vec<_> = fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect();
-    let data_matrix = Matrix::new(data);
+    ///Render the grid showing only the chars at the given cells, every other char is replaced by a '.'
+    ///(this is the view the puzzle uses to show where the matches are)
+    pub fn render_highlighted(&self, cells: impl IntoIterator<Item = (usize, usize)>) -> String {
+        let mut highlighted = vec![vec![false; self.col_num]; self.row_num];
+        for (row_index, col_index) in cells {
+            highlighted[row_index][col_index] = true;
+        }
 
-    //We now scan the data_matrix and whenever we find an X we check all possible directions for the rest of the letters
+        self.data
+            .iter()
+            .zip(highlighted)
+            .map(|(row, highlighted_row)| {
+                row.iter()
+                    .zip(highlighted_row)
+                    .map(|(&char, is_highlighted)| if is_highlighted { char } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
-    let mut xmas_counter: usize = 0;
+    ///Find number of times XMAS appears where the X appears in current row and col index (looks in all directions)
+    pub fn find_xmas(&self, row_index: usize, col_index: usize) -> usize {
+        self.find_word_at((row_index, col_index), "XMAS", &Direction::ALL)
+            .len()
+    }
 
-    for (row_index, current_row) in data_matrix.data.iter().enumerate() {
-        for (col_index, current_char) in current_row.iter().enumerate() {
-            if *current_char == 'X' {
-                //Call function to check all directions and return number of matches
-                xmas_counter += data_matrix.find_xmas(row_index, col_index);
-            }
+    ///Find if there is an X shaped MAS centered at this point, returning 1 if there is and 0 otherwise
+    pub fn find_x_shaped_mas(&self, row_index: usize, col_index: usize) -> usize {
+        //The center of the template is 1 step down-right from its top left corner
+        if row_index == 0 || col_index == 0 {
+            return 0;
         }
+
+        self.find_template_at(
+            (row_index - 1, col_index - 1),
+            &x_shaped_mas(),
+            &Orientation::ROTATIONS,
+        )
+        .len()
     }
+}
 
-    xmas_counter
+///The X shaped MAS template. Its 4 rotations are exactly the 4 ways to write an X shaped MAS.
+fn x_shaped_mas() -> Template {
+    Template::parse("M.S\n.A.\nM.S", '.')
 }
 
-///Reads the input text and returns the number of occurences of X *Shaped* MAS
-///(Note we are guaranteed the input is rectanguler)
-fn solution_part2(file_path: &str) -> usize {
+///Reads the input text into a Matrix (Note we are guaranteed the input is rectanguler)
+fn read_matrix(file_path: &str) -> Matrix {
     let data: Vec<_> = fs::read_to_string(file_path)
         .expect("failed to open file")
         .lines()
         .map(|line| line.chars().collect::<Vec<char>>())
         .collect();
-    let data_matrix = Matrix::new(data);
-
-    //We now scan the data_matrix and whenever we find an A we check all possible directions for the rest of the letters
-
-    let mut x_counter: usize = 0;
+    Matrix::new(data)
+}
 
-    for (row_index, current_row) in data_matrix.data.iter().enumerate() {
-        for (col_index, current_char) in current_row.iter().enumerate() {
-            if *current_char == 'A' {
-                //Call function to check all directions and return number of matches
-                x_counter += data_matrix.find_x_shaped_mas(row_index, col_index);
-            }
-        }
-    }
+///Reads the input text and returns the number of occurences of XMAS (Note we are guaranteed the input is rectanguler)
+fn solution_part1(file_path: &str) -> usize {
+    read_matrix(file_path)
+        .find_word("XMAS", &Direction::ALL)
+        .len()
+}
 
-    x_counter
+///Reads the input text and returns the number of occurences of X *Shaped* MAS
+///(Note we are guaranteed the input is rectanguler)
+fn solution_part2(file_path: &str) -> usize {
+    read_matrix(file_path)
+        .find_template(&x_shaped_mas(), &Orientation::ROTATIONS)
+        .len()
 }
 
 #[cfg(test)]
@@ -214,6 +444,21 @@ mod tests {
 
     use super::*;
 
+    const EXAMPLE: &str = "MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX";
+
+    fn example_matrix() -> Matrix {
+        Matrix::new(EXAMPLE.lines().map(|line| line.chars().collect()).collect())
+    }
+
     #[test]
     fn answer() {
         dbg!(solution_part1("puzzle_inputs/day4.txt"));
@@ -231,4 +476,86 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day4example_part2.txt");
         assert_eq!(result, 9);
     }
+
+    #[test]
+    fn generic_search() {
+        let matrix = example_matrix();
+
+        assert_eq!(matrix.find_word("XMAS", &Direction::ALL).len(), 18);
+        assert_eq!(
+            matrix
+                .find_template(&x_shaped_mas(), &Orientation::ROTATIONS)
+                .len(),
+            9
+        );
+
+        //The old per-cell helpers agree with the generic search
+        let mut xmas_counter = 0;
+        let mut x_counter = 0;
+        for row_index in 0..matrix.row_num {
+            for col_index in 0..matrix.col_num {
+                xmas_counter += matrix.find_xmas(row_index, col_index);
+                x_counter += matrix.find_x_shaped_mas(row_index, col_index);
+            }
+        }
+        assert_eq!((xmas_counter, x_counter), (18, 9));
+
+        //XMAS read left to right from the top row
+        assert!(
+            matrix
+                .find_word("XMAS", &Direction::STRAIGHT)
+                .contains(&WordMatch {
+                    start: (0, 5),
+                    direction: Direction::Right,
+                    cells: vec![(0, 5), (0, 6), (0, 7), (0, 8)],
+                })
+        );
+
+        //A symmetric template is only matched once per location
+        let all_a = Template::parse("A", '.');
+        let a_count = EXAMPLE.chars().filter(|&char| char == 'A').count();
+        assert_eq!(
+            matrix.find_template(&all_a, &Orientation::ALL).len(),
+            a_count
+        );
+    }
+
+    #[test]
+    fn highlighted_rendering() {
+        let matrix = example_matrix();
+
+        let xmas_matches = matrix.find_word("XMAS", &Direction::ALL);
+        let rendered =
+            matrix.render_highlighted(xmas_matches.iter().flat_map(|m| m.cells.iter().copied()));
+        assert_eq!(
+            rendered,
+            "....XXMAS.
+.SAMXMS...
+...S..A...
+..A.A.MS.X
+XMASAMX.MM
+X.....XA.A
+S.S.S.S.SS
+.A.A.A.A.A
+..M.M.M.MM
+.X.X.XMASX"
+        );
+
+        let x_matches = matrix.find_template(&x_shaped_mas(), &Orientation::ROTATIONS);
+        let rendered =
+            matrix.render_highlighted(x_matches.iter().flat_map(|m| m.cells.iter().copied()));
+        assert_eq!(
+            rendered,
+            ".M.S......
+..A..MSMS.
+.M.S.MAA..
+..A.ASMSM.
+.M.S.M....
+..........
+S.S.S.S.S.
+.A.A.A.A..
+M.M.M.M.M.
+.........."
+        );
+    }
 }
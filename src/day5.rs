@@ -1,139 +1,441 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs;
 
 //The idea for part 1: build a Hash map where key is page_number and value is pages that must come before page_number.
 //Parse each update as a vector.
 //Iterate on the update vector and for each entry check it is not in vector must_come_before
 //and if check successful then append table(entry) to must_come_before
+//
+//The RuleSet holds the same rules as a graph (an edge X -> Y for each rule X|Y) which lets us
+//find a topological order of the pages in an update, and notice when there isn't exactly one.
 
-pub struct Update {
-    data: Vec<usize>,
+///Why the pages of an update could not be put in a single order
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OrderError {
+    ///The rules restricted to the update contain a cycle. Holds the pages of the cycle in order,
+    ///that is each page must come before the next one (and the last one before the first).
+    Cycle(Vec<usize>),
+    ///The rules restricted to the update only form a partial order. Holds pages that could each
+    ///come next at the first point where there is more than one choice.
+    Ambiguous(Vec<usize>),
 }
 
-impl Update {
-    pub fn new(data: Vec<usize>) -> Update {
-        Update { data }
+///A set of page ordering rules, stored as a graph where each rule X|Y is an edge X -> Y
+#[derive(Debug, Default, Clone)]
+pub struct RuleSet {
+    //the key is some page number X, the value is all pages Y such that X|Y
+    must_come_after: HashMap<usize, HashSet<usize>>,
+    //the key is some page number Y, the value is all pages X such that X|Y
+    must_come_before: HashMap<usize, HashSet<usize>>,
+}
+
+impl RuleSet {
+    pub fn new() -> RuleSet {
+        RuleSet::default()
     }
 
-    ///Finds out if this update is correct or not according to some list of rules (given as a Hash Table)
-    pub fn is_correct(&self, rule_set: &HashMap<usize, Vec<usize>>) -> bool {
-        //a vector of pages we must not encounter.
+    ///Add the rule before|after
+    pub fn add_rule(&mut self, before: usize, after: usize) {
+        self.must_come_after
+            .entry(before)
+            .or_default()
+            .insert(after);
+        self.must_come_before
+            .entry(after)
+            .or_default()
+            .insert(before);
+    }
+
+    ///Returns true if there is a rule before|after
+    pub fn has_rule(&self, before: usize, after: usize) -> bool {
+        self.must_come_after
+            .get(&before)
+            .is_some_and(|pages| pages.contains(&after))
+    }
+
+    ///Finds out if the pages are in a correct order according to the rules
+    pub fn is_correct(&self, pages: &[usize]) -> bool {
+        //a set of pages we must not encounter.
         //if X|Y then once we encounter Y we must not encouter X later in the update
-        let mut must_not_see = vec![];
+        let mut must_not_see: HashSet<usize> = HashSet::new();
 
-        for page in &self.data {
+        for page in pages {
             if must_not_see.contains(page) {
                 return false;
-            } else if let Some(extension_value) = rule_set.get(page) {
+            } else if let Some(extension_value) = self.must_come_before.get(page) {
                 must_not_see.extend(extension_value);
             }
         }
 
-        //if we got here that means this update is correct
+        //if we got here that means the pages are in a correct order
         true
     }
 
-    ///Corrects the update according to the rule set
-    pub fn correct_update(&mut self, rule_set: &HashMap<usize, Vec<usize>>) {
-        'outer: loop {
-            //the key is some page number X, the value is the first index in self.data for a page number Y
-            //such that X|Y
-            let mut offending_index: HashMap<_, _> = HashMap::new();
-
-            for index in 0..self.data.len() {
-                let page = self.data[index];
-                if let Some(first_index) = offending_index.get(&page) {
-                    //this update violates X|Y. That is this page X comes after some page Y.
-                    //We attempt to fix this update by inserting X *before* the index of the first such Y
-                    //since a correct ordering is *guaranteed* to exist, we know this terminates
-                    //(after swapping i and j, we know sorting i to j w.r.t to the rule_set terminates
-                    //[you can see why by induction] and
-                    //then the first place problems can occur is at index j+1- so slowly this entire process terminates).
-                    //What we do works on the same principle but I think it is more efficent.
-
-                    //Note first_index < index
-                    self.data.remove(index); // Note self.data[index] = page
-                    self.data.insert(*first_index, page);
-                    continue 'outer;
-                } else if let Some(extension_values) = rule_set.get(&page) {
-                    for entry in extension_values {
-                        offending_index.entry(entry).or_insert(index);
+    ///Returns a topological order of pages according to the rules restricted to pages
+    ///(we assume the pages are distinct).
+    ///
+    ///The order is deterministic: whenever several pages could come next we pick the one
+    ///that appears first in pages. If strict is true we instead return OrderError::Ambiguous
+    ///when that happens, so the order is only returned if it is the only one possible.
+    fn order(&self, pages: &[usize], strict: bool) -> Result<Vec<usize>, OrderError> {
+        let index_of: HashMap<usize, usize> = pages
+            .iter()
+            .enumerate()
+            .map(|(index, &page)| (page, index))
+            .collect();
+
+        //the number of (not yet placed) pages in the update that must come before each page
+        let mut in_degree: Vec<usize> = pages
+            .iter()
+            .map(|page| {
+                self.must_come_before.get(page).map_or(0, |before| {
+                    before
+                        .iter()
+                        .filter(|page| index_of.contains_key(page))
+                        .count()
+                })
+            })
+            .collect();
+
+        //indices of pages that could be placed next (smallest index first)
+        let mut ready: BinaryHeap<Reverse<usize>> = in_degree
+            .iter()
+            .enumerate()
+            .filter(|&(_, &degree)| degree == 0)
+            .map(|(index, _)| Reverse(index))
+            .collect();
+
+        let mut ordered = Vec::with_capacity(pages.len());
+        while let Some(Reverse(index)) = ready.pop() {
+            if strict && !ready.is_empty() {
+                let mut choices: Vec<usize> = ready.iter().map(|Reverse(other)| *other).collect();
+                choices.push(index);
+                choices.sort_unstable();
+                return Err(OrderError::Ambiguous(
+                    choices.into_iter().map(|index| pages[index]).collect(),
+                ));
+            }
+
+            let page = pages[index];
+            ordered.push(page);
+            if let Some(after) = self.must_come_after.get(&page) {
+                for next_page in after {
+                    if let Some(&next_index) = index_of.get(next_page) {
+                        in_degree[next_index] -= 1;
+                        if in_degree[next_index] == 0 {
+                            ready.push(Reverse(next_index));
+                        }
                     }
                 }
             }
+        }
+
+        if ordered.len() < pages.len() {
+            //Every page we could not place has at least one unplaced page that must come before it,
+            //so walking backwards along such pages must eventually repeat a page
+            let unplaced: HashSet<usize> = in_degree
+                .iter()
+                .enumerate()
+                .filter(|&(_, &degree)| degree > 0)
+                .map(|(index, _)| pages[index])
+                .collect();
+
+            let mut walk = vec![*unplaced.iter().min().unwrap()];
+            loop {
+                let current = *walk.last().unwrap();
+                let previous = *self.must_come_before[&current]
+                    .iter()
+                    .filter(|page| unplaced.contains(page))
+                    .min()
+                    .unwrap();
 
-            //if we got here that means this update is correct
-            return;
+                if let Some(start) = walk.iter().position(|&page| page == previous) {
+                    //we walked backwards, so reverse to list the cycle in "must come before" order
+                    let mut cycle = walk.split_off(start);
+                    cycle.reverse();
+                    //start the cycle at its smallest page so the report is deterministic
+                    let smallest = (0..cycle.len()).min_by_key(|&index| cycle[index]).unwrap();
+                    cycle.rotate_left(smallest);
+                    return Err(OrderError::Cycle(cycle));
+                }
+                walk.push(previous);
+            }
         }
+
+        Ok(ordered)
+    }
+
+    ///Returns a deterministic topological order of pages according to the rules
+    ///(if several pages could come next, the one appearing first in pages is picked).
+    ///Returns OrderError::Cycle if there is no such order.
+    pub fn topological_order(&self, pages: &[usize]) -> Result<Vec<usize>, OrderError> {
+        self.order(pages, false)
+    }
+
+    ///Returns the order of pages according to the rules only if the rules restricted to pages form a
+    ///total order (that is, there is exactly one correct order).
+    ///Returns OrderError::Cycle or OrderError::Ambiguous otherwise.
+    pub fn total_order(&self, pages: &[usize]) -> Result<Vec<usize>, OrderError> {
+        self.order(pages, true)
     }
 }
 
-///Returns the sum of the middle page numbers of correct updates (doesn't correct incorrect updates)
-fn solution_part1(file_path: &str) -> usize {
-    let mut rule_set: HashMap<_, _> = HashMap::new();
+///Moving page from index from in the update to index to in the corrected update
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PageMove {
+    pub page: usize,
+    pub from: usize,
+    pub to: usize,
+}
 
-    let mut correct_mid_page_sum = 0;
+///Explains why an update was rejected and how it was corrected
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CorrectionReport {
+    ///Every rule X|Y (given as (X, Y)) such that Y comes before X in the update
+    pub violated_rules: Vec<(usize, usize)>,
+    ///The fewest page moves that turn the update into corrected (every other page stays in its relative order)
+    pub moves: Vec<PageMove>,
+    ///The corrected update
+    pub corrected: Vec<usize>,
+}
+
+pub struct Update {
+    data: Vec<usize>,
+}
 
-    for line in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-    {
+impl Update {
+    pub fn new(data: Vec<usize>) -> Update {
+        Update { data }
+    }
+
+    ///Finds out if this update is correct or not according to some rule set
+    pub fn is_correct(&self, rule_set: &RuleSet) -> bool {
+        rule_set.is_correct(&self.data)
+    }
+
+    ///Returns every rule X|Y (as (X, Y)) that this update violates, that is Y comes before X in the update.
+    ///The rules are listed in the order of the index of Y and then the index of X.
+    pub fn violated_rules(&self, rule_set: &RuleSet) -> Vec<(usize, usize)> {
+        let mut violated = Vec::new();
+        for (index, &earlier) in self.data.iter().enumerate() {
+            for &later in &self.data[index + 1..] {
+                if rule_set.has_rule(later, earlier) {
+                    violated.push((later, earlier));
+                }
+            }
+        }
+        violated
+    }
+
+    ///Returns the violated rules, the corrected update and the fewest page moves needed to get there.
+    ///
+    ///The pages that stay are a largest set of pages whose current relative order can be kept by some
+    ///correct order (see Update::pages_to_keep), the corrected update is then a topological order of the
+    ///rules together with that relative order. So the number of moves is the fewest over every correct order,
+    ///not just the one RuleSet::topological_order picks.
+    pub fn correction_report(&self, rule_set: &RuleSet) -> Result<CorrectionReport, OrderError> {
+        //make sure the rules themselves have an order before we look for pages to keep
+        rule_set.topological_order(&self.data)?;
+
+        let stays = self.pages_to_keep(rule_set);
+
+        //the pages that stay must keep their relative order, so we add a rule between each pair of them
+        let mut keep_order = rule_set.clone();
+        let kept: Vec<usize> = (0..self.data.len())
+            .filter(|&index| stays[index])
+            .map(|index| self.data[index])
+            .collect();
+        for pair in kept.windows(2) {
+            keep_order.add_rule(pair[0], pair[1]);
+        }
+        //this can't fail, see Update::pages_to_keep
+        let corrected = keep_order.topological_order(&self.data)?;
+
+        let moves = self
+            .data
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| !stays[index])
+            .map(|(index, &page)| PageMove {
+                page,
+                from: index,
+                to: corrected.iter().position(|other| *other == page).unwrap(),
+            })
+            .collect();
+
+        Ok(CorrectionReport {
+            violated_rules: self.violated_rules(rule_set),
+            moves,
+            corrected,
+        })
+    }
+
+    ///Returns (for each index of the update) whether the page stays in place in a correction with the fewest moves.
+    ///
+    ///A set of pages can keep its relative order exactly when there are no two of them X before Y in the update
+    ///such that Y must (maybe through other pages of the update) come before X. Calling such X and Y "conflicting",
+    ///conflicts form a partial order on the update (if Z conflicts with Y and Y with X then Z conflicts with X),
+    ///so we are looking for a largest antichain. By Dilworth's and König's theorems we find it from a maximum
+    ///matching of the bipartite graph with an edge X -> Y for every conflict, where X is the earlier page.
+    fn pages_to_keep(&self, rule_set: &RuleSet) -> Vec<bool> {
+        let length = self.data.len();
+        let index_of: HashMap<usize, usize> = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, &page)| (page, index))
+            .collect();
+
+        //reaches[i][j] is true if the page at index i must come before the page at index j
+        let mut reaches = vec![vec![false; length]; length];
+        for (start, row) in reaches.iter_mut().enumerate() {
+            let mut stack = vec![start];
+            while let Some(index) = stack.pop() {
+                for next_page in rule_set
+                    .must_come_after
+                    .get(&self.data[index])
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(&next_index) = index_of.get(next_page)
+                        && !row[next_index]
+                    {
+                        row[next_index] = true;
+                        stack.push(next_index);
+                    }
+                }
+            }
+        }
+
+        //conflicts[i] are the later indices j whose page must come before the page at index i
+        let conflicts: Vec<Vec<usize>> = (0..length)
+            .map(|earlier| {
+                (earlier + 1..length)
+                    .filter(|&later| reaches[later][earlier])
+                    .collect()
+            })
+            .collect();
+
+        //Kuhn's algorithm: matched_to[j] is the earlier index matched to the later index j
+        fn augment(
+            earlier: usize,
+            conflicts: &[Vec<usize>],
+            matched_to: &mut [Option<usize>],
+            visited: &mut [bool],
+        ) -> bool {
+            for &later in &conflicts[earlier] {
+                if !visited[later] {
+                    visited[later] = true;
+                    if matched_to[later]
+                        .is_none_or(|other| augment(other, conflicts, matched_to, visited))
+                    {
+                        matched_to[later] = Some(earlier);
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+
+        let mut matched_to: Vec<Option<usize>> = vec![None; length];
+        let is_matched: Vec<bool> = (0..length)
+            .map(|earlier| {
+                augment(
+                    earlier,
+                    &conflicts,
+                    &mut matched_to,
+                    &mut vec![false; length],
+                )
+            })
+            .collect();
+
+        //König: walk alternating paths from the unmatched earlier sides (conflict edges forward,
+        //matching edges backward). A page is in the antichain if its earlier side was reached but its
+        //later side wasn't.
+        let mut reached_earlier = vec![false; length];
+        let mut reached_later = vec![false; length];
+        let mut stack: Vec<usize> = (0..length).filter(|&index| !is_matched[index]).collect();
+        for &index in &stack {
+            reached_earlier[index] = true;
+        }
+        while let Some(earlier) = stack.pop() {
+            for &later in &conflicts[earlier] {
+                if !reached_later[later] {
+                    reached_later[later] = true;
+                    if let Some(other) = matched_to[later]
+                        && !reached_earlier[other]
+                    {
+                        reached_earlier[other] = true;
+                        stack.push(other);
+                    }
+                }
+            }
+        }
+
+        (0..length)
+            .map(|index| reached_earlier[index] && !reached_later[index])
+            .collect()
+    }
+
+    ///Corrects the update according to the rule set.
+    ///If the rules only give a partial order, pages keep their relative order where the rules allow it.
+    pub fn correct_update(&mut self, rule_set: &RuleSet) -> Result<(), OrderError> {
+        self.data = rule_set.topological_order(&self.data)?;
+        Ok(())
+    }
+}
+
+///Parse the puzzle input into the rule set and the list of updates
+fn parse_input(input: &str) -> (RuleSet, Vec<Update>) {
+    let mut rule_set = RuleSet::new();
+    let mut updates = Vec::new();
+
+    for line in input.lines() {
         if let Some((raw_x, raw_y)) = line.split_once('|') {
-            //means the line is X|Y so we want to add X to the list of pages that must come before Y
-            let (value, key): (usize, usize) = (raw_x.parse().unwrap(), raw_y.parse().unwrap());
-            rule_set
-                .entry(key)
-                .and_modify(|vec: &mut Vec<usize>| vec.push(value))
-                .or_insert(vec![value]);
+            //means the line is X|Y so X must come before Y
+            rule_set.add_rule(raw_x.parse().unwrap(), raw_y.parse().unwrap());
         } else if !line.is_empty() {
             //means the line is an update (a comma seperated list of numbers)
             let update = line
                 .split(',')
                 .map(|raw| raw.parse::<usize>().unwrap())
                 .collect::<Vec<_>>();
-            let update = Update::new(update);
-            if update.is_correct(&rule_set) {
-                //we are guaranteed updates have an odd length and in rust integer divisions are rounded down automatically
-                let mid_index = update.data.len() / 2;
-                correct_mid_page_sum += update.data[mid_index];
-            }
+            updates.push(Update::new(update));
         }
     }
 
-    correct_mid_page_sum
+    (rule_set, updates)
+}
+
+///Returns the sum of the middle page numbers of correct updates (doesn't correct incorrect updates)
+fn solution_part1(file_path: &str) -> usize {
+    let (rule_set, updates) =
+        parse_input(&fs::read_to_string(file_path).expect("failed to open file"));
+
+    updates
+        .iter()
+        .filter(|update| update.is_correct(&rule_set))
+        //we are guaranteed updates have an odd length and in rust integer divisions are rounded down automatically
+        .map(|update| update.data[update.data.len() / 2])
+        .sum()
 }
 
 ///Returns the sum of the middle page numbers of *just* incorrect updates after correcting them
 fn solution_part2(file_path: &str) -> usize {
-    let mut rule_set: HashMap<_, _> = HashMap::new();
+    let (rule_set, updates) =
+        parse_input(&fs::read_to_string(file_path).expect("failed to open file"));
 
     let mut mid_page_sum = 0;
-
-    for line in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-    {
-        if let Some((raw_x, raw_y)) = line.split_once('|') {
-            //means the line is X|Y so we want to add X to the list of pages that must come before Y
-            let (value, key): (usize, usize) = (raw_x.parse().unwrap(), raw_y.parse().unwrap());
-            rule_set
-                .entry(key)
-                .and_modify(|vec: &mut Vec<usize>| vec.push(value))
-                .or_insert(vec![value]);
-        } else if !line.is_empty() {
-            //means the line is an update (a comma seperated list of numbers)
-            let update = line
-                .split(',')
-                .map(|raw| raw.parse::<usize>().unwrap())
-                .collect::<Vec<_>>();
-            let mut update = Update::new(update);
-            if !update.is_correct(&rule_set) {
-                //correct the update
-                update.correct_update(&rule_set);
-                //we are guaranteed updates have an odd length and in rust integer divisions are rounded down automatically
-                let mid_index = update.data.len() / 2;
-                mid_page_sum += update.data[mid_index];
-            }
+    for mut update in updates {
+        if !update.is_correct(&rule_set) {
+            //correct the update
+            update
+                .correct_update(&rule_set)
+                .expect("rules for an update contain a cycle");
+            //we are guaranteed updates have an odd length and in rust integer divisions are rounded down automatically
+            let mid_index = update.data.len() / 2;
+            mid_page_sum += update.data[mid_index];
         }
     }
 
@@ -145,6 +447,35 @@ mod tests {
 
     use super::*;
 
+    const EXAMPLE: &str = "47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,61,53,29,13
+75,29,13
+75,97,47,61,53
+61,13,29
+97,13,75,29,47";
+
     #[test]
     fn answer() {
         dbg!(solution_part1("puzzle_inputs/day5.txt"));
@@ -162,4 +493,115 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day5example.txt");
         assert_eq!(result, 123);
     }
+
+    #[test]
+    fn rule_set_orders() {
+        let (rule_set, updates) = parse_input(EXAMPLE);
+
+        let correct: Vec<bool> = updates
+            .iter()
+            .map(|update| update.is_correct(&rule_set))
+            .collect();
+        assert_eq!(correct, vec![true, true, true, false, false, false]);
+
+        let mut mid_page_sum = 0;
+        for mut update in updates {
+            if !update.is_correct(&rule_set) {
+                update.correct_update(&rule_set).unwrap();
+                mid_page_sum += update.data[update.data.len() / 2];
+            }
+        }
+        assert_eq!(mid_page_sum, 123);
+
+        //The example rules give a total order for every update
+        assert_eq!(
+            rule_set.total_order(&[97, 13, 75, 29, 47]),
+            Ok(vec![97, 75, 47, 29, 13])
+        );
+        assert_eq!(rule_set.total_order(&[61, 13, 29]), Ok(vec![61, 29, 13]));
+
+        //No rule between 1 and 2: a partial order keeps the given relative order
+        let mut partial = RuleSet::new();
+        partial.add_rule(3, 1);
+        partial.add_rule(3, 2);
+        assert_eq!(partial.topological_order(&[2, 1, 3]), Ok(vec![3, 2, 1]));
+        assert_eq!(
+            partial.total_order(&[2, 1, 3]),
+            Err(OrderError::Ambiguous(vec![2, 1]))
+        );
+
+        //4 -> 5 -> 6 -> 4 is a cycle, 7 just hangs off it
+        let mut cyclic = RuleSet::new();
+        cyclic.add_rule(4, 5);
+        cyclic.add_rule(5, 6);
+        cyclic.add_rule(6, 4);
+        cyclic.add_rule(6, 7);
+        assert_eq!(
+            cyclic.topological_order(&[7, 6, 5, 4]),
+            Err(OrderError::Cycle(vec![4, 5, 6]))
+        );
+    }
+
+    #[test]
+    fn correction_report() {
+        let (rule_set, updates) = parse_input(EXAMPLE);
+
+        let report = updates[3].correction_report(&rule_set).unwrap();
+        assert_eq!(report.violated_rules, vec![(97, 75)]);
+        assert_eq!(
+            report.moves,
+            vec![PageMove {
+                page: 75,
+                from: 0,
+                to: 1
+            }]
+        );
+        assert_eq!(report.corrected, vec![97, 75, 47, 61, 53]);
+
+        //97,13,75,29,47 -> 97,75,47,29,13 keeps 97, 75 and 47 in place
+        let report = updates[5].correction_report(&rule_set).unwrap();
+        assert_eq!(
+            report.violated_rules,
+            vec![(75, 13), (29, 13), (47, 13), (47, 29)]
+        );
+        assert_eq!(
+            report.moves,
+            vec![
+                PageMove {
+                    page: 13,
+                    from: 1,
+                    to: 4
+                },
+                PageMove {
+                    page: 29,
+                    from: 3,
+                    to: 3
+                }
+            ]
+        );
+
+        //The moves are the fewest over every correct order, not just the one topological_order picks
+        let mut rule_set = RuleSet::new();
+        rule_set.add_rule(0, 2);
+        rule_set.add_rule(0, 3);
+        let report = Update::new(vec![3, 2, 1, 0])
+            .correction_report(&rule_set)
+            .unwrap();
+        assert_eq!(report.violated_rules, vec![(0, 3), (0, 2)]);
+        assert_eq!(
+            report.moves,
+            vec![PageMove {
+                page: 0,
+                from: 3,
+                to: 0
+            }]
+        );
+        assert_eq!(report.corrected, vec![0, 3, 2, 1]);
+
+        //A correct update needs no moves
+        let (rule_set, updates) = parse_input(EXAMPLE);
+        let report = updates[0].correction_report(&rule_set).unwrap();
+        assert!(report.violated_rules.is_empty() && report.moves.is_empty());
+        assert_eq!(report.corrected, updates[0].data);
+    }
 }
@@ -1,7 +1,6 @@
 use std::fs;
 
 mod guard {
-    use std::cell::RefCell;
     use std::collections::HashSet;
 
     //We use Matrix as helper struct to GuardPatrol
@@ -28,280 +27,732 @@ mod guard {
                 col_num,
             }
         }
+
+        ///Returns the location 1 step from location in direction.
+        ///If that is out of the bounds of the map we return None, unless wraparound is true
+        ///in which case the step continues from the opposite edge.
+        fn step(
+            &self,
+            location: (usize, usize),
+            direction: GuardDirection,
+            wraparound: bool,
+        ) -> Option<(usize, usize)> {
+            let (row_delta, col_delta) = direction.delta();
+
+            if wraparound {
+                let row_index = (location.0 + self.row_num).checked_add_signed(row_delta)?;
+                let col_index = (location.1 + self.col_num).checked_add_signed(col_delta)?;
+                Some((row_index % self.row_num, col_index % self.col_num))
+            } else {
+                let row_index = location
+                    .0
+                    .checked_add_signed(row_delta)
+                    .filter(|&row_index| row_index < self.row_num)?;
+                let col_index = location
+                    .1
+                    .checked_add_signed(col_delta)
+                    .filter(|&col_index| col_index < self.col_num)?;
+                Some((row_index, col_index))
+            }
+        }
     }
 
-    #[derive(Hash, PartialEq, Eq, Clone, Copy)]
-    enum GuardDirection {
+    //Note the variants are in clockwise order, which is what turning relies on
+    #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+    pub enum GuardDirection {
         Up,
+        UpRight,
+        Right,
+        DownRight,
         Down,
+        DownLeft,
         Left,
+        UpLeft,
+    }
+
+    impl GuardDirection {
+        const ALL: [GuardDirection; 8] = [
+            GuardDirection::Up,
+            GuardDirection::UpRight,
+            GuardDirection::Right,
+            GuardDirection::DownRight,
+            GuardDirection::Down,
+            GuardDirection::DownLeft,
+            GuardDirection::Left,
+            GuardDirection::UpLeft,
+        ];
+
+        const STRAIGHT: [GuardDirection; 4] = [
+            GuardDirection::Up,
+            GuardDirection::Right,
+            GuardDirection::Down,
+            GuardDirection::Left,
+        ];
+
+        ///The index of this direction in GuardDirection::ALL
+        fn index(self) -> usize {
+            self as usize
+        }
+
+        ///The (row, col) change of taking 1 step in this direction
+        fn delta(self) -> (isize, isize) {
+            match self {
+                GuardDirection::Up => (-1, 0),
+                GuardDirection::UpRight => (-1, 1),
+                GuardDirection::Right => (0, 1),
+                GuardDirection::DownRight => (1, 1),
+                GuardDirection::Down => (1, 0),
+                GuardDirection::DownLeft => (1, -1),
+                GuardDirection::Left => (0, -1),
+                GuardDirection::UpLeft => (-1, -1),
+            }
+        }
+
+        ///The direction after turning clockwise by eighths * 45 degrees
+        fn rotate(self, eighths: usize) -> GuardDirection {
+            GuardDirection::ALL[(self.index() + eighths) % GuardDirection::ALL.len()]
+        }
+
+        ///The direction after turning 180 degrees
+        fn reverse(self) -> GuardDirection {
+            self.rotate(4)
+        }
+
+        ///The direction a guard drawn on the map with glyph faces (None if glyph is not a guard)
+        fn from_glyph(glyph: char) -> Option<GuardDirection> {
+            match glyph {
+                '^' => Some(GuardDirection::Up),
+                '>' => Some(GuardDirection::Right),
+                'v' => Some(GuardDirection::Down),
+                '<' => Some(GuardDirection::Left),
+                _ => None,
+            }
+        }
+    }
+
+    ///Which way the guard turns when there is an obstacle in front of them
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum TurnRule {
         Right,
+        Left,
+        Reverse,
     }
 
-    pub struct GuardPatrol {
-        guard_location: (usize, usize),
-        guard_direction: GuardDirection,
-        map: RefCell<Matrix>,
-        //Note GuardPatrol does not implement Sync (because of RefCell).
-        //This is needed because of the count_unique_trap_locations method.
+    ///The rules a guard follows on their patrol
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct PatrolRules {
+        pub turn: TurnRule,
+        ///If true the guard can also face (and move) diagonally, so turning left or right is by 45 degrees
+        ///instead of 90 degrees
+        pub diagonal: bool,
+        ///If true a guard walking off an edge of the map comes back in from the opposite edge
+        ///(so they never exit the map)
+        pub wraparound: bool,
+        ///If true a guard treats another guard right in front of them as an obstacle.
+        ///Only the lockstep simulation (see GuardPatrol::lockstep) takes the other guards into account.
+        pub guards_block: bool,
     }
 
-    impl GuardPatrol {
-        ///Find the location of the guard in a map.
-        ///That is find indices (i,j) such that self.data at (i,j) =='^'
-        fn find_guard(map: &Matrix) -> Result<(usize, usize), &'static str> {
-            for (row_index, row) in map.data.iter().enumerate() {
-                for (col_index, char) in row.iter().enumerate() {
-                    if *char == '^' {
-                        return Ok((row_index, col_index));
-                    }
-                }
+    impl Default for PatrolRules {
+        ///The rules from the puzzle: turn 90 degrees right, no diagonal movement, exit at the edges
+        fn default() -> PatrolRules {
+            PatrolRules {
+                turn: TurnRule::Right,
+                diagonal: false,
+                wraparound: false,
+                guards_block: false,
             }
+        }
+    }
 
-            Err("Could not find guard")
+    impl PatrolRules {
+        ///The direction after the guard turns in front of an obstacle
+        fn turn(&self, direction: GuardDirection) -> GuardDirection {
+            let quarter = if self.diagonal { 1 } else { 2 };
+            match self.turn {
+                TurnRule::Right => direction.rotate(quarter),
+                TurnRule::Left => direction.rotate(8 - quarter),
+                TurnRule::Reverse => direction.reverse(),
+            }
         }
 
-        pub fn new(map: Vec<Vec<char>>) -> GuardPatrol {
-            let map = RefCell::new(Matrix::new(map));
+        ///The directions a guard can face under these rules
+        fn directions(&self) -> &'static [GuardDirection] {
+            if self.diagonal {
+                &GuardDirection::ALL
+            } else {
+                &GuardDirection::STRAIGHT
+            }
+        }
+    }
 
-            //The guard is initially facing up
-            let guard_direction = GuardDirection::Up;
+    ///Something that happened during a patrol.
+    ///Each event holds the location of the guard and the direction they face right after it happened.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum PatrolEvent {
+        ///The guard moved 1 step forward
+        Moved {
+            location: (usize, usize),
+            direction: GuardDirection,
+        },
+        ///There was an obstacle in front of the guard so they turned instead
+        Turned {
+            location: (usize, usize),
+            direction: GuardDirection,
+        },
+        ///The guard walked out of the bounds of the map (location is the last location in bounds).
+        ///This is always the last event (and never happens with wraparound rules).
+        Exited {
+            location: (usize, usize),
+            direction: GuardDirection,
+        },
+        ///The guard is back at a location facing a direction they already had there, so they will
+        ///patrol forever. This is always the last event.
+        LoopDetected {
+            location: (usize, usize),
+            direction: GuardDirection,
+        },
+    }
 
-            let guard_location = GuardPatrol::find_guard(&map.borrow()).unwrap();
+    ///A single record of a patrol trace: (row, col, direction)
+    pub type TraceRecord = (usize, usize, GuardDirection);
 
-            GuardPatrol {
-                guard_location,
-                guard_direction,
-                map,
+    ///Move a guard at location facing direction 1 step (or turn) according to the rules.
+    ///A location ahead is an obstacle if it holds a '#' or if is_occupied returns true for it.
+    ///Returns a Moved, Turned or Exited event.
+    fn advance(
+        map: &Matrix,
+        rules: PatrolRules,
+        location: (usize, usize),
+        direction: GuardDirection,
+        is_occupied: impl Fn((usize, usize)) -> bool,
+    ) -> PatrolEvent {
+        match map.step(location, direction, rules.wraparound) {
+            //The guard goes out of bounds
+            None => PatrolEvent::Exited {
+                location,
+                direction,
+            },
+            //if there is an obstacle in front of the guard the guard turns instead
+            Some(ahead) if map.data[ahead.0][ahead.1] == '#' || is_occupied(ahead) => {
+                PatrolEvent::Turned {
+                    location,
+                    direction: rules.turn(direction),
+                }
             }
+            Some(ahead) => PatrolEvent::Moved {
+                location: ahead,
+                direction,
+            },
         }
+    }
+
+    ///An iterator over the events of a patrol. Created by GuardPatrol::patrol.
+    pub struct Patrol<'a> {
+        map: &'a Matrix,
+        rules: PatrolRules,
+        location: (usize, usize),
+        direction: GuardDirection,
+        //Each (location, direction) the guard has had so far.
+        //If we ever go through the same place in the same direction we did before, then we know
+        //the guard is on a loop and thus is going to be patroling forever
+        travel_log: HashSet<((usize, usize), GuardDirection)>,
+        finished: bool,
+    }
+
+    impl Iterator for Patrol<'_> {
+        type Item = PatrolEvent;
 
-        ///Advance the guard by 1 step (or turn) if possible,
-        ///replacing the current position in the map with an 'X'.
-        ///Returns Ok(()) if the guard is still in bounds of the map and Err("out_of_bounds") otherwise.
-        fn traverse_with_trace(&mut self) -> Result<(), &'static str> {
-            //first replace the current positon of the guard in the map with an X
-            let mut map = self.map.borrow_mut();
-            map.data[self.guard_location.0][self.guard_location.1] = 'X';
+        fn next(&mut self) -> Option<PatrolEvent> {
+            if self.finished {
+                return None;
+            }
 
             //Move the guard 1 step (or turn) according to the direction the guard faces
-            match self.guard_direction {
-                GuardDirection::Up => {
-                    //Check if 1 step up is not out of the bounds of the map
-                    if self.guard_location.0 > 0 {
-                        //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                        if map.data[self.guard_location.0 - 1][self.guard_location.1] == '#' {
-                            self.guard_direction = GuardDirection::Right;
-                        } else {
-                            //move the guard 1 step up
-                            self.guard_location =
-                                (self.guard_location.0 - 1, self.guard_location.1);
-                        }
-                    } else {
-                        //The guard goes out of bounds
-                        return Err("out_of_bounds");
+            let event = advance(self.map, self.rules, self.location, self.direction, |_| {
+                false
+            });
+            match event {
+                PatrolEvent::Moved {
+                    location,
+                    direction,
+                }
+                | PatrolEvent::Turned {
+                    location,
+                    direction,
+                } => (self.location, self.direction) = (location, direction),
+                _ => {
+                    self.finished = true;
+                    return Some(event);
+                }
+            }
+
+            //Note insert returns whether the value was newly inserted.
+            if !self.travel_log.insert((self.location, self.direction)) {
+                //The guard is on a loop
+                self.finished = true;
+                return Some(PatrolEvent::LoopDetected {
+                    location: self.location,
+                    direction: self.direction,
+                });
+            }
+
+            Some(event)
+        }
+    }
+
+    ///The location and direction of a guard
+    pub type GuardState = ((usize, usize), GuardDirection);
+
+    ///An iterator over the events of all guards patroling in lockstep. Created by GuardPatrol::lockstep.
+    ///
+    ///Each item is one tick: every guard still patroling moves 1 step (or turns), in the order the guards
+    ///were found in the map. The item holds the event of each guard (None for guards that already exited
+    ///or looped). The iterator ends once every guard exited or looped.
+    pub struct Lockstep<'a> {
+        map: &'a Matrix,
+        rules: PatrolRules,
+        //The state of each guard, None once the guard exited or looped
+        guards: Vec<Option<GuardState>>,
+        //An extra obstacle that is not in the map
+        obstruction: Option<(usize, usize)>,
+        //If guards don't block each other, each guard loops on their own when one of their states repeats.
+        //Otherwise the guards only loop when the states of all of them repeat together.
+        travel_logs: Vec<HashSet<GuardState>>,
+        joint_travel_log: HashSet<Vec<Option<GuardState>>>,
+    }
+
+    impl Iterator for Lockstep<'_> {
+        type Item = Vec<Option<PatrolEvent>>;
+
+        fn next(&mut self) -> Option<Vec<Option<PatrolEvent>>> {
+            if self.guards.iter().all(Option::is_none) {
+                return None;
+            }
+
+            let mut events = vec![None; self.guards.len()];
+            for (guard_index, guard_event) in events.iter_mut().enumerate() {
+                let Some((location, direction)) = self.guards[guard_index] else {
+                    continue;
+                };
+
+                let guards = &self.guards;
+                let is_occupied = |ahead: (usize, usize)| {
+                    Some(ahead) == self.obstruction
+                        || (self.rules.guards_block
+                            && guards.iter().enumerate().any(|(other_index, other)| {
+                                other_index != guard_index
+                                    && other
+                                        .is_some_and(|(other_location, _)| other_location == ahead)
+                            }))
+                };
+
+                let event = advance(self.map, self.rules, location, direction, is_occupied);
+                self.guards[guard_index] = match event {
+                    PatrolEvent::Moved {
+                        location,
+                        direction,
                     }
+                    | PatrolEvent::Turned {
+                        location,
+                        direction,
+                    } => Some((location, direction)),
+                    _ => None,
+                };
+                *guard_event = Some(event);
+            }
+
+            //Find out which guards are now on a loop
+            let mut looped = vec![];
+            if self.rules.guards_block {
+                if !self.joint_travel_log.insert(self.guards.clone()) {
+                    looped.extend(0..self.guards.len());
                 }
-                GuardDirection::Down => {
-                    //Check if 1 step down is not out of the bounds of the map
-                    if self.guard_location.0 + 1 < map.row_num {
-                        //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                        if map.data[self.guard_location.0 + 1][self.guard_location.1] == '#' {
-                            self.guard_direction = GuardDirection::Left;
-                        } else {
-                            //move the guard 1 step down
-                            self.guard_location =
-                                (self.guard_location.0 + 1, self.guard_location.1);
+            } else {
+                for (guard_index, guard) in self.guards.iter().enumerate() {
+                    if let Some(state) = guard {
+                        //Note insert returns whether the value was newly inserted.
+                        if !self.travel_logs[guard_index].insert(*state) {
+                            looped.push(guard_index);
                         }
-                    } else {
-                        //The guard goes out of bounds
-                        return Err("out_of_bounds");
                     }
                 }
-                GuardDirection::Right => {
-                    //Check if 1 step right is not out of the bounds of the map
-                    if self.guard_location.1 + 1 < map.col_num {
-                        //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                        if map.data[self.guard_location.0][self.guard_location.1 + 1] == '#' {
-                            self.guard_direction = GuardDirection::Down;
-                        } else {
-                            //move the guard 1 step right
-                            self.guard_location =
-                                (self.guard_location.0, self.guard_location.1 + 1);
+            }
+
+            for guard_index in looped {
+                if let Some((location, direction)) = self.guards[guard_index].take() {
+                    events[guard_index] = Some(PatrolEvent::LoopDetected {
+                        location,
+                        direction,
+                    });
+                }
+            }
+
+            Some(events)
+        }
+    }
+
+    ///How many distinct locations the guards have been on their patrols
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub struct Coverage {
+        ///The number of distinct locations each guard has been (in the order the guards were found)
+        pub per_guard: Vec<usize>,
+        ///The number of distinct locations at least one guard has been
+        pub combined: usize,
+    }
+
+    ///Where a guard ends up when walking straight from some location
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    enum Jump {
+        ///The guard stops at this location since there is an obstacle right in front of it
+        Blocked((usize, usize)),
+        ///There is no obstacle ahead so the guard walks out of the bounds of the map
+        Exits,
+        ///There is no obstacle ahead and the guard wraps around the map, so they walk forever
+        Endless,
+    }
+
+    ///For every location and direction, the next obstacle the guard walking that way runs into.
+    ///This lets a patrol jump straight from one turn to the next instead of walking step by step.
+    struct JumpTable {
+        //jumps[direction.index()][row_index][col_index] (empty for directions the rules never use)
+        jumps: Vec<Vec<Vec<Jump>>>,
+        rules: PatrolRules,
+    }
+
+    impl JumpTable {
+        fn new(map: &Matrix, rules: PatrolRules) -> JumpTable {
+            let mut jumps = vec![vec![]; GuardDirection::ALL.len()];
+
+            for &direction in rules.directions() {
+                let mut table: Vec<Vec<Option<Jump>>> = vec![vec![None; map.col_num]; map.row_num];
+
+                for row_index in 0..map.row_num {
+                    for col_index in 0..map.col_num {
+                        //Walk forward until we reach a location whose jump we already know (or we find out
+                        //where the walk ends), then every location on the way shares that same jump
+                        let mut walked = vec![];
+                        let mut location = (row_index, col_index);
+                        let jump = loop {
+                            if let Some(jump) = table[location.0][location.1] {
+                                break jump;
+                            }
+                            //Note with wraparound a step is a bijection, so a walk that never runs into
+                            //an obstacle comes back to where it started
+                            if location == (row_index, col_index) && !walked.is_empty() {
+                                break Jump::Endless;
+                            }
+                            walked.push(location);
+                            match map.step(location, direction, rules.wraparound) {
+                                None => break Jump::Exits,
+                                Some(ahead) if map.data[ahead.0][ahead.1] == '#' => {
+                                    break Jump::Blocked(location);
+                                }
+                                Some(ahead) => location = ahead,
+                            }
+                        };
+
+                        for (row_index, col_index) in walked {
+                            table[row_index][col_index] = Some(jump);
                         }
-                    } else {
-                        //The guard goes out of bounds
-                        return Err("out_of_bounds");
                     }
                 }
 
-                GuardDirection::Left => {
-                    //Check if 1 step left is not out of the bounds of the map
-                    if self.guard_location.1 > 0 {
-                        //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                        if map.data[self.guard_location.0][self.guard_location.1 - 1] == '#' {
-                            self.guard_direction = GuardDirection::Up;
-                        } else {
-                            //move the guard 1 step left
-                            self.guard_location =
-                                (self.guard_location.0, self.guard_location.1 - 1);
-                        }
-                    } else {
-                        //The guard goes out of bounds
-                        return Err("out_of_bounds");
+                jumps[direction.index()] = table
+                    .into_iter()
+                    .map(|row| row.into_iter().map(Option::unwrap).collect())
+                    .collect();
+            }
+
+            JumpTable { jumps, rules }
+        }
+
+        fn get(&self, location: (usize, usize), direction: GuardDirection) -> Jump {
+            self.jumps[direction.index()][location.0][location.1]
+        }
+
+        ///Update the table as if there were an obstacle at obstruction.
+        ///Only the locations that walk into the obstruction (that is the ones in between it and the previous
+        ///obstacle in each direction) change. Returns the overwritten entries so they can be restored.
+        fn insert_obstruction(
+            &mut self,
+            map: &Matrix,
+            obstruction: (usize, usize),
+        ) -> Vec<(GuardDirection, (usize, usize), Jump)> {
+            let mut overwritten = vec![];
+
+            let wraparound = self.rules.wraparound;
+            for &direction in self.rules.directions() {
+                let Some(stop) = map.step(obstruction, direction.reverse(), wraparound) else {
+                    continue;
+                };
+
+                //walk backwards from the obstruction, every location we pass now stops at stop
+                //(with wraparound we might come all the way back to the obstruction)
+                let mut location = stop;
+                while map.data[location.0][location.1] != '#' && location != obstruction {
+                    let entry = &mut self.jumps[direction.index()][location.0][location.1];
+                    overwritten.push((direction, location, *entry));
+                    *entry = Jump::Blocked(stop);
+
+                    match map.step(location, direction.reverse(), wraparound) {
+                        Some(behind) => location = behind,
+                        None => break,
                     }
                 }
             }
 
-            Ok(())
+            overwritten
         }
 
-        ///Make the guard do a full patrol, leaving a trace.
-        ///Returns the number of distinct locations (including the initial guard location) the guard has been
-        pub fn full_patrol(&mut self) -> usize {
-            //traverse untill the error (guard goes out of bounds)
-            //we are guaranteed the guard will go out of bounds at some point
-            while self.traverse_with_trace().is_ok() {}
+        ///Undo insert_obstruction
+        fn restore(&mut self, overwritten: Vec<(GuardDirection, (usize, usize), Jump)>) {
+            for (direction, location, jump) in overwritten {
+                self.jumps[direction.index()][location.0][location.1] = jump;
+            }
+        }
+    }
 
-            //count (and return) the number of X's in the map after the patrol ended (guard went out of bounds)
-            self.map
-                .borrow()
-                .data
-                .iter()
-                .flatten()
-                .filter(|&&char| char == 'X')
-                .count()
-        }
-
-        ///find out if a guard patrol starting at self.guard_location with inital direction
-        ///self.guard_direction loops foever or ends.
-        ///Return 1 if it loops forever and 0 otherwise.
-        fn obstructed_patrol(&self) -> usize {
-            let map = self.map.borrow();
-            let mut current_location = self.guard_location;
-            let mut current_direction = self.guard_direction;
-            let mut travel_log = HashSet::new();
+    pub struct GuardPatrol {
+        //Note the guards are in the order they appear in the map (row by row)
+        guards: Vec<GuardState>,
+        map: Matrix,
+        rules: PatrolRules,
+    }
 
-            //This terminates as the guard either patrols forever or they don't, meaning the guard goes out of
-            //the bounds of the map at some point
-            loop {
-                //We create a new set (travel_log) and each step we move we record
-                //in the travel log the direction we went (for example if at (i,j) we moved up
-                //then add (i,j, GuardDirection::Up) to set of movements we made).
-                //
-                //If we ever go through the same place in the same direction we did before, then we know
-                //the guard is on a loop and thus is going to be patroling forever
-
-                //Note insert returns whether the value was newly inserted.
-                if !travel_log.insert((current_location, current_direction)) {
-                    //The guard is on a loop
-                    return 1;
+    impl GuardPatrol {
+        ///Find the locations of the guards in a map, along with the direction each of them faces.
+        ///That is find indices (i,j) such that self.data at (i,j) is one of '^', '>', 'v' or '<'
+        fn find_guards(map: &Matrix) -> Result<Vec<GuardState>, &'static str> {
+            let mut guards = vec![];
+            for (row_index, row) in map.data.iter().enumerate() {
+                for (col_index, char) in row.iter().enumerate() {
+                    if let Some(direction) = GuardDirection::from_glyph(*char) {
+                        guards.push(((row_index, col_index), direction));
+                    }
                 }
+            }
 
-                //Move the guard 1 step (or turn) according to the direction the guard faces
-                match current_direction {
-                    GuardDirection::Up => {
-                        //Check if 1 step up is not out of the bounds of the map
-                        if current_location.0 > 0 {
-                            //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                            if map.data[current_location.0 - 1][current_location.1] == '#' {
-                                current_direction = GuardDirection::Right;
-                            } else {
-                                //move the guard 1 step up
-                                current_location = (current_location.0 - 1, current_location.1);
-                            }
-                        } else {
-                            //The guard goes out of bounds
-                            return 0;
-                        }
+            if guards.is_empty() {
+                Err("Could not find guard")
+            } else {
+                Ok(guards)
+            }
+        }
+
+        ///A patrol following the rules from the puzzle
+        pub fn new(map: Vec<Vec<char>>) -> GuardPatrol {
+            GuardPatrol::with_rules(map, PatrolRules::default())
+        }
+
+        pub fn with_rules(map: Vec<Vec<char>>, rules: PatrolRules) -> GuardPatrol {
+            let map = Matrix::new(map);
+
+            let guards = GuardPatrol::find_guards(&map).unwrap();
+
+            GuardPatrol { guards, map, rules }
+        }
+
+        ///The initial location and direction of each guard
+        pub fn guards(&self) -> &[GuardState] {
+            &self.guards
+        }
+
+        ///Returns an iterator over the events of the patrol of the guard at guard_index,
+        ///as if there were no other guards.
+        ///The iterator ends once the guard exits the map or a loop is detected.
+        pub fn patrol(&self, guard_index: usize) -> Patrol<'_> {
+            let (location, direction) = self.guards[guard_index];
+            let mut travel_log = HashSet::new();
+            travel_log.insert((location, direction));
+
+            Patrol {
+                map: &self.map,
+                rules: self.rules,
+                location,
+                direction,
+                travel_log,
+                finished: false,
+            }
+        }
+
+        ///Returns an iterator over the events of all the guards patroling together in lockstep
+        pub fn lockstep(&self) -> Lockstep<'_> {
+            self.lockstep_with_obstruction(None)
+        }
+
+        ///Same as lockstep, with an extra obstacle at obstruction (if it is not None)
+        fn lockstep_with_obstruction(&self, obstruction: Option<(usize, usize)>) -> Lockstep<'_> {
+            let guards: Vec<Option<GuardState>> = self.guards.iter().copied().map(Some).collect();
+
+            Lockstep {
+                map: &self.map,
+                rules: self.rules,
+                travel_logs: self
+                    .guards
+                    .iter()
+                    .map(|&state| HashSet::from([state]))
+                    .collect(),
+                joint_travel_log: HashSet::from([guards.clone()]),
+                guards,
+                obstruction,
+            }
+        }
+
+        ///Returns the full trace of the patrol of the guard at guard_index (as if there were no other guards):
+        ///the initial (row, col, direction) of the guard followed by the (row, col, direction) after every
+        ///step and turn.
+        ///If the guard loops, the trace ends at the first repeated record (which is included).
+        pub fn trace(&self, guard_index: usize) -> Vec<TraceRecord> {
+            let (location, direction) = self.guards[guard_index];
+            let mut trace = vec![(location.0, location.1, direction)];
+
+            for event in self.patrol(guard_index) {
+                match event {
+                    PatrolEvent::Moved {
+                        location,
+                        direction,
                     }
-                    GuardDirection::Down => {
-                        //Check if 1 step down is not out of the bounds of the map
-                        if current_location.0 + 1 < map.row_num {
-                            //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                            if map.data[current_location.0 + 1][current_location.1] == '#' {
-                                current_direction = GuardDirection::Left;
-                            } else {
-                                //move the guard 1 step down
-                                current_location = (current_location.0 + 1, current_location.1);
-                            }
-                        } else {
-                            //The guard goes out of bounds
-                            return 0;
-                        }
+                    | PatrolEvent::Turned {
+                        location,
+                        direction,
                     }
-                    GuardDirection::Right => {
-                        //Check if 1 step right is not out of the bounds of the map
-                        if current_location.1 + 1 < map.col_num {
-                            //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                            if map.data[current_location.0][current_location.1 + 1] == '#' {
-                                current_direction = GuardDirection::Down;
-                            } else {
-                                //move the guard 1 step right
-                                current_location = (current_location.0, current_location.1 + 1);
-                            }
-                        } else {
-                            //The guard goes out of bounds
-                            return 0;
-                        }
+                    | PatrolEvent::LoopDetected {
+                        location,
+                        direction,
+                    } => trace.push((location.0, location.1, direction)),
+                    PatrolEvent::Exited { .. } => {}
+                }
+            }
+
+            trace
+        }
+
+        ///The distinct locations (including the initial location) the guard at guard_index has been
+        ///on their patrol (as if there were no other guards)
+        fn path(&self, guard_index: usize) -> HashSet<(usize, usize)> {
+            //Note if the guard loops the trace still covers every location they will ever be
+            self.trace(guard_index)
+                .into_iter()
+                .map(|(row_index, col_index, _)| (row_index, col_index))
+                .collect()
+        }
+
+        ///Make all the guards patrol together in lockstep.
+        ///Returns the number of distinct locations each guard has been and the number of distinct locations
+        ///any guard has been (including the initial guard locations)
+        pub fn coverage(&self) -> Coverage {
+            let mut visited: Vec<HashSet<(usize, usize)>> = self
+                .guards
+                .iter()
+                .map(|&(location, _)| HashSet::from([location]))
+                .collect();
+
+            for events in self.lockstep() {
+                for (guard_index, event) in events.into_iter().enumerate() {
+                    if let Some(PatrolEvent::Moved { location, .. }) = event {
+                        visited[guard_index].insert(location);
                     }
+                }
+            }
 
-                    GuardDirection::Left => {
-                        //Check if 1 step left is not out of the bounds of the map
-                        if current_location.1 > 0 {
-                            //if there is an obstacle in front of the guard the guard turns 90 degrees right instead
-                            if map.data[current_location.0][current_location.1 - 1] == '#' {
-                                current_direction = GuardDirection::Up;
-                            } else {
-                                //move the guard 1 step left
-                                current_location = (current_location.0, current_location.1 - 1);
-                            }
-                        } else {
-                            //The guard goes out of bounds
-                            return 0;
+            Coverage {
+                per_guard: visited.iter().map(HashSet::len).collect(),
+                combined: visited.iter().flatten().collect::<HashSet<_>>().len(),
+            }
+        }
+
+        ///Make the guards do a full patrol.
+        ///Returns the number of distinct locations (including the initial guard locations) any guard has been
+        pub fn full_patrol(&self) -> usize {
+            self.coverage().combined
+        }
+
+        ///find out if the patrol of the guard at guard_index loops foever or ends,
+        ///using the jump table to go from turn to turn.
+        fn jumping_patrol_loops(&self, jump_table: &JumpTable, guard_index: usize) -> bool {
+            let (mut current_location, mut current_direction) = self.guards[guard_index];
+            //Each (location, direction) the guard had right before a turn.
+            //The patrol is determined by these, so if one repeats the guard is on a loop.
+            let mut turn_log = HashSet::new();
+
+            loop {
+                match jump_table.get(current_location, current_direction) {
+                    Jump::Exits => return false,
+                    Jump::Endless => return true,
+                    Jump::Blocked(stop) => {
+                        current_location = stop;
+                        //Note insert returns whether the value was newly inserted.
+                        if !turn_log.insert((current_location, current_direction)) {
+                            return true;
                         }
+                        current_direction = self.rules.turn(current_direction);
                     }
                 }
             }
         }
 
         ///Get the number of distinct locations one can place an obstruction that
-        ///will cause the guard to patrol forever
+        ///will cause all the guards to patrol forever
         pub fn count_unique_trap_locations(&self) -> usize {
-            //We try placing the obstruction at each possible free location (not '^' or '#')
-            //and observe if the guard then patrols forever
+            let free_locations = (0..self.map.row_num)
+                .flat_map(|row_index| {
+                    (0..self.map.col_num).map(move |col_index| (row_index, col_index))
+                })
+                .filter(|&(row_index, col_index)| self.map.data[row_index][col_index] == '.');
+
+            if self.rules.guards_block {
+                //The guards affect each other's patrols, so we simulate all of them together
+                //with the obstruction at each possible free location
+                return free_locations
+                    .filter(|&obstruction| {
+                        self.lockstep_with_obstruction(Some(obstruction))
+                            .flatten()
+                            .flatten()
+                            .all(|event| !matches!(event, PatrolEvent::Exited { .. }))
+                    })
+                    .count();
+            }
+
+            //Otherwise each guard patrols on their own. An obstruction a guard never walks into can't change
+            //their patrol, so we only need the jump table for the guards whose original path has the obstruction
+            let paths: Vec<HashSet<(usize, usize)>> = (0..self.guards.len())
+                .map(|guard_index| self.path(guard_index))
+                .collect();
+            let originally_loops: Vec<bool> = (0..self.guards.len())
+                .map(|guard_index| {
+                    matches!(
+                        self.patrol(guard_index).last(),
+                        Some(PatrolEvent::LoopDetected { .. })
+                    )
+                })
+                .collect();
+
+            let mut jump_table = JumpTable::new(&self.map, self.rules);
             let mut counter = 0;
 
-            let (row_num, col_num) = {
-                let map = self.map.borrow();
-                (map.row_num, map.col_num)
-            };
+            for obstruction in free_locations {
+                let (affected, unaffected): (Vec<usize>, Vec<usize>) = (0..self.guards.len())
+                    .partition(|&guard_index| paths[guard_index].contains(&obstruction));
 
-            //Note:
-            //Logically this method is immutable and sound (in a single threaded context)
-            //since the mutations we do on the map are temporary.
-            //That is the whole reason why we wrapped the Matrix in a RefCell.
-            //Note GuardPatrol does not implement Sync.
-            //Alternative approaches would be
-            //to make this method take a mutable reference (but this method is logically immutable)
-            //or clone the map (not efficent).
-
-            for row_index in 0..row_num {
-                for col_index in 0..col_num {
-                    if self.map.borrow().data[row_index][col_index] == '.' {
-                        //Note self.obstructed_patrol() borrows the RefCell immutably which is why this
-                        //is a bit clunky
-
-                        //place a temporary obstruction at this char's location (note the mutable borrow
-                        //of the refcell does not live past this line)
-                        self.map.borrow_mut().data[row_index][col_index] = '#';
-
-                        counter += self.obstructed_patrol();
-
-                        //remove temporary obstruction at this char's location
-                        self.map.borrow_mut().data[row_index][col_index] = '.';
-                    }
+                if !unaffected
+                    .iter()
+                    .all(|&guard_index| originally_loops[guard_index])
+                {
+                    continue;
                 }
+
+                //place a temporary obstruction at this location, then remove it again
+                let overwritten = jump_table.insert_obstruction(&self.map, obstruction);
+
+                if affected
+                    .iter()
+                    .all(|&guard_index| self.jumping_patrol_loops(&jump_table, guard_index))
+                {
+                    counter += 1;
+                }
+
+                jump_table.restore(overwritten);
             }
 
             counter
@@ -317,7 +768,7 @@ fn solution_part1(file_path: &str) -> usize {
         .map(|line| line.chars().collect::<Vec<char>>())
         .collect();
 
-    let mut patrol = guard::GuardPatrol::new(data);
+    let patrol = guard::GuardPatrol::new(data);
 
     patrol.full_patrol()
 }
@@ -358,4 +809,238 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day6example.txt");
         assert_eq!(result, 6);
     }
+
+    const EXAMPLE: &str = "....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    fn example_patrol() -> guard::GuardPatrol {
+        guard::GuardPatrol::new(EXAMPLE.lines().map(|line| line.chars().collect()).collect())
+    }
+
+    ///Count the trap locations the slow way: place an obstruction on every free location and
+    ///check the full step by step patrol with is_trapped
+    fn brute_force_trap_locations(
+        map: &[Vec<char>],
+        rules: guard::PatrolRules,
+        is_trapped: impl Fn(&guard::GuardPatrol) -> bool,
+    ) -> usize {
+        let mut brute_force = 0;
+        for row_index in 0..map.len() {
+            for col_index in 0..map[0].len() {
+                if map[row_index][col_index] == '.' {
+                    let mut obstructed = map.to_vec();
+                    obstructed[row_index][col_index] = '#';
+                    if is_trapped(&guard::GuardPatrol::with_rules(obstructed, rules)) {
+                        brute_force += 1;
+                    }
+                }
+            }
+        }
+        brute_force
+    }
+
+    ///A single guard is trapped if their patrol ends in a loop
+    fn ends_in_loop(patrol: &guard::GuardPatrol) -> bool {
+        matches!(
+            patrol.patrol(0).last(),
+            Some(guard::PatrolEvent::LoopDetected { .. })
+        )
+    }
+
+    #[test]
+    fn patrol_events_and_trace() {
+        use guard::{GuardDirection, PatrolEvent};
+
+        let patrol = example_patrol();
+        assert_eq!(patrol.full_patrol(), 41);
+        assert_eq!(patrol.count_unique_trap_locations(), 6);
+
+        let events: Vec<_> = patrol.patrol(0).collect();
+        assert_eq!(
+            events[..6],
+            [
+                PatrolEvent::Moved {
+                    location: (5, 4),
+                    direction: GuardDirection::Up
+                },
+                PatrolEvent::Moved {
+                    location: (4, 4),
+                    direction: GuardDirection::Up
+                },
+                PatrolEvent::Moved {
+                    location: (3, 4),
+                    direction: GuardDirection::Up
+                },
+                PatrolEvent::Moved {
+                    location: (2, 4),
+                    direction: GuardDirection::Up
+                },
+                PatrolEvent::Moved {
+                    location: (1, 4),
+                    direction: GuardDirection::Up
+                },
+                PatrolEvent::Turned {
+                    location: (1, 4),
+                    direction: GuardDirection::Right
+                },
+            ]
+        );
+        assert_eq!(
+            events.last(),
+            Some(&PatrolEvent::Exited {
+                location: (9, 7),
+                direction: GuardDirection::Down
+            })
+        );
+
+        //The trace has the initial record plus one record per step or turn
+        let trace = patrol.trace(0);
+        assert_eq!(trace.len(), events.len());
+        assert_eq!(trace[0], (6, 4, GuardDirection::Up));
+        assert_eq!(trace.last(), Some(&(9, 7, GuardDirection::Down)));
+
+        //Placing an obstruction next to the guard's starting position makes them loop
+        let mut map: Vec<Vec<char>> = EXAMPLE.lines().map(|line| line.chars().collect()).collect();
+        map[6][3] = '#';
+        let looping = guard::GuardPatrol::new(map);
+        assert!(matches!(
+            looping.patrol(0).last(),
+            Some(PatrolEvent::LoopDetected { .. })
+        ));
+    }
+
+    #[test]
+    fn jump_table_matches_step_by_step() {
+        let map: Vec<Vec<char>> = EXAMPLE.lines().map(|line| line.chars().collect()).collect();
+        let brute_force =
+            brute_force_trap_locations(&map, guard::PatrolRules::default(), ends_in_loop);
+
+        assert_eq!(brute_force, 6);
+        assert_eq!(example_patrol().count_unique_trap_locations(), brute_force);
+    }
+
+    #[test]
+    fn configurable_rules() {
+        use guard::{PatrolEvent, PatrolRules, TurnRule};
+
+        let map: Vec<Vec<char>> = EXAMPLE.lines().map(|line| line.chars().collect()).collect();
+
+        //Check the jump table agrees with step by step patrols for a set of rules
+        let check_rules = |rules: PatrolRules| {
+            let brute_force = brute_force_trap_locations(&map, rules, ends_in_loop);
+
+            let patrol = guard::GuardPatrol::with_rules(map.clone(), rules);
+            assert_eq!(patrol.count_unique_trap_locations(), brute_force);
+            patrol
+        };
+
+        for turn in [TurnRule::Right, TurnRule::Left, TurnRule::Reverse] {
+            for diagonal in [false, true] {
+                for wraparound in [false, true] {
+                    check_rules(PatrolRules {
+                        turn,
+                        diagonal,
+                        wraparound,
+                        guards_block: false,
+                    });
+                }
+            }
+        }
+
+        //With wraparound the guard never exits
+        let patrol = check_rules(PatrolRules {
+            wraparound: true,
+            ..PatrolRules::default()
+        });
+        assert!(matches!(
+            patrol.patrol(0).last(),
+            Some(PatrolEvent::LoopDetected { .. })
+        ));
+
+        //Turning left the guard first walks up and turns at (1, 4), then walks left off the map at (1, 0)
+        let patrol = check_rules(PatrolRules {
+            turn: TurnRule::Left,
+            ..PatrolRules::default()
+        });
+        assert_eq!(patrol.full_patrol(), 10);
+    }
+
+    #[test]
+    fn multiple_guards() {
+        use guard::{GuardDirection, PatrolEvent, PatrolRules};
+
+        //Two guards walking towards each other along the middle row
+        let map: Vec<Vec<char>> = [".....", ">...<", "....."]
+            .iter()
+            .map(|line| line.chars().collect())
+            .collect();
+
+        //Without interaction they pass through each other and both cover the middle row
+        let patrol = guard::GuardPatrol::new(map.clone());
+        assert_eq!(
+            patrol.guards(),
+            [
+                ((1, 0), GuardDirection::Right),
+                ((1, 4), GuardDirection::Left)
+            ]
+        );
+        let coverage = patrol.coverage();
+        assert_eq!((coverage.per_guard, coverage.combined), (vec![5, 5], 5));
+
+        //When guards block each other the second guard turns right (up) in front of the first one,
+        //then the first guard turns right (down) in front of the second one
+        let patrol = guard::GuardPatrol::with_rules(
+            map,
+            PatrolRules {
+                guards_block: true,
+                ..PatrolRules::default()
+            },
+        );
+        let ticks: Vec<_> = patrol.lockstep().collect();
+        assert_eq!(
+            ticks[1],
+            vec![
+                Some(PatrolEvent::Moved {
+                    location: (1, 2),
+                    direction: GuardDirection::Right
+                }),
+                Some(PatrolEvent::Turned {
+                    location: (1, 3),
+                    direction: GuardDirection::Up
+                })
+            ]
+        );
+        let coverage = patrol.coverage();
+        assert_eq!((coverage.per_guard, coverage.combined), (vec![4, 3], 7));
+
+        //Add a second guard (facing down) to the example. An obstruction only counts if it traps both guards.
+        let mut map: Vec<Vec<char>> = EXAMPLE.lines().map(|line| line.chars().collect()).collect();
+        map[2][6] = 'v';
+        for (guards_block, expected) in [(false, 5), (true, 3)] {
+            let rules = PatrolRules {
+                guards_block,
+                ..PatrolRules::default()
+            };
+
+            let brute_force = brute_force_trap_locations(&map, rules, |patrol| {
+                patrol
+                    .lockstep()
+                    .flatten()
+                    .flatten()
+                    .all(|event| !matches!(event, PatrolEvent::Exited { .. }))
+            });
+
+            assert_eq!(brute_force, expected);
+            let patrol = guard::GuardPatrol::with_rules(map.clone(), rules);
+            assert_eq!(patrol.count_unique_trap_locations(), brute_force);
+        }
+    }
 }
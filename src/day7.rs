@@ -1,139 +1,499 @@
 use std::fs;
 pub mod equation {
+    use std::fmt;
+    use std::num::{IntErrorKind, ParseIntError};
+    use std::str::FromStr;
 
-    ///Returns whether the equation can evaluate to the goal if we select correct operations (operations available: + and *)
-    pub fn is_true(goal: usize, current_value: usize, equation: &str) -> bool {
-        //first we get the next number from the equation
-        let (next_operand_raw, equation, equation_over) = match equation.split_once(" ") {
-            Some((next_operand_raw, equation)) => (next_operand_raw, equation, false),
-            None => (equation, "", true), //meaning we got to the end of the equation it now contains just the final number
-        };
+    ///Returned when a number (or an intermediate value) does not fit in the number type we work with
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Overflow;
 
-        let next_operand = next_operand_raw.parse::<usize>().unwrap();
+    ///The unsigned number types an equation can be solved with.
+    ///All the arithmetic is checked, returning None on overflow.
+    pub trait Number: Copy + Eq + fmt::Display + FromStr<Err = ParseIntError> {
+        const ZERO: Self;
+        fn checked_add(self, other: Self) -> Option<Self>;
+        fn checked_sub(self, other: Self) -> Option<Self>;
+        fn checked_mul(self, other: Self) -> Option<Self>;
+        ///Returns self / other, or None if other is 0 or does not divide self
+        fn checked_exact_div(self, other: Self) -> Option<Self>;
+        ///Returns self % other, or None if other is 0
+        fn checked_rem(self, other: Self) -> Option<Self>;
+        ///10 to the power of the number of digits in self (None if that overflows)
+        fn digit_shift(self) -> Option<Self>;
+    }
+
+    macro_rules! impl_number {
+        ($($number:ty),*) => {$(
+            impl Number for $number {
+                const ZERO: Self = 0;
 
-        let (add_result, mul_result) = (current_value + next_operand, current_value * next_operand);
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$number>::checked_add(self, other)
+                }
+
+                fn checked_sub(self, other: Self) -> Option<Self> {
+                    <$number>::checked_sub(self, other)
+                }
 
-        if equation_over {
-            //return whether adding or multiplying the final operand got us to the goal
-            goal == add_result || goal == mul_result
-        } else {
-            //Short circuting: If both adding and multipying the current operand exceeds the goal than
-            //this equation cannot be true.
-            //Note we can't rely on just adding exceeding the goal because the current operand
-            //can be 1 and num +1 > num *1. Note that we know all the number in the equation are positive whole numbers (no 0's).
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$number>::checked_mul(self, other)
+                }
+
+                fn checked_exact_div(self, other: Self) -> Option<Self> {
+                    if other != 0 && self.is_multiple_of(other) {
+                        Some(self / other)
+                    } else {
+                        None
+                    }
+                }
 
-            //we can optimize this more
-            //i.e. if  goal < mul_result then no point in running is_true(goal, mul_result, equation).
-            if goal < add_result && goal < mul_result {
-                return false;
+                fn checked_rem(self, other: Self) -> Option<Self> {
+                    <$number>::checked_rem(self, other)
+                }
+
+                fn digit_shift(self) -> Option<Self> {
+                    <$number>::checked_pow(10, self.checked_ilog10().unwrap_or(0) + 1)
+                }
             }
+        )*};
+    }
+
+    impl_number!(usize, u128);
+
+    ///The left operands that give some result with an operator (and a fixed right operand)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Inverse<N: Number = usize> {
+        ///No left operand gives the result
+        NoLeft,
+        ///Only this left operand gives the result
+        Left(N),
+        ///Every left operand gives the result, like left * 0 = 0
+        AnyLeft,
+    }
+
+    ///An operator that can be placed between two numbers (of type N) of an equation.
+    ///
+    ///The solver works right-to-left: knowing the result and the last operand it asks the operator which left
+    ///operand would give that result. So for a fixed right operand, different left operands must give different
+    ///results, unless every left operand gives the same result (like multiplying by 0).
+    pub trait Operator<N: Number = usize> {
+        ///The symbol used when rendering an equation, like "+"
+        fn symbol(&self) -> &str;
 
-            is_true(goal, add_result, equation) || is_true(goal, mul_result, equation)
+        ///Returns left (operator) right, or None if that is not a valid number (for example it overflows)
+        fn apply(&self, left: N, right: N) -> Option<N>;
+
+        ///Returns the left operands such that left (operator) right == result.
+        ///This is what lets the solver prune: most of the time there is no such left.
+        ///
+        ///Returns Err(Overflow) if there is such a left but it does not fit in N.
+        fn invert(&self, result: N, right: N) -> Result<Inverse<N>, Overflow>;
+    }
+
+    ///Addition (+). Inverted by subtraction.
+    pub struct Add;
+
+    impl<N: Number> Operator<N> for Add {
+        fn symbol(&self) -> &str {
+            "+"
+        }
+
+        fn apply(&self, left: N, right: N) -> Option<N> {
+            left.checked_add(right)
+        }
+
+        fn invert(&self, result: N, right: N) -> Result<Inverse<N>, Overflow> {
+            Ok(result
+                .checked_sub(right)
+                .map_or(Inverse::NoLeft, Inverse::Left))
         }
     }
 
-    ///Returns whether the equation can evaluate to the goal if we select correct operations (operations available: +, *, ||).
-    pub fn is_true_inc_concat(goal: usize, current_value: Option<usize>, equation: &str) -> bool {
-        //first we get the next number from the equation
-        let (next_operand_raw, equation, equation_over) = match equation.split_once(" ") {
-            Some((next_operand_raw, equation)) => (next_operand_raw, equation, false),
-            None => (equation, "", true), //meaning we got to the end of the equation it now contains just the final number
-        };
+    ///Multiplication (*). Inverted by division, which is only possible if right divides the result.
+    pub struct Mul;
 
-        //We now can add, multiply or concat the next number to the current value
-        let next_operand = next_operand_raw.parse::<usize>().unwrap();
+    impl<N: Number> Operator<N> for Mul {
+        fn symbol(&self) -> &str {
+            "*"
+        }
 
-        match current_value {
-            Some(current_value) => {
-                let concat_result = format!("{}{}", current_value, next_operand_raw)
-                    .parse::<usize>()
-                    .unwrap();
-                let (add_result, mul_result) =
-                    (current_value + next_operand, current_value * next_operand);
+        fn apply(&self, left: N, right: N) -> Option<N> {
+            left.checked_mul(right)
+        }
 
-                if equation_over {
-                    goal == add_result || goal == mul_result || goal == concat_result
+        fn invert(&self, result: N, right: N) -> Result<Inverse<N>, Overflow> {
+            //Note left * 0 is 0 for any left, so we can't divide by it
+            if right == N::ZERO {
+                return Ok(if result == N::ZERO {
+                    Inverse::AnyLeft
                 } else {
-                    //Short circuting: If both adding and multipying the current operand exceeds the goal than
-                    //this equation cannot be true.
-                    //Note we can't rely on just adding exceeding the goal because the current operand
-                    //can be 1 and num +1 > num *1. Note that we know all the number in the equation are positive whole numbers (no 0's).
-                    //Note that concating num2 to num1 is effectively = num1 * (10**number of digits in num2) + num2.
-                    //So in particular concat_result > add_result.
-                    if goal < add_result && goal < mul_result {
-                        return false;
-                    }
+                    Inverse::NoLeft
+                });
+            }
+            Ok(result
+                .checked_exact_div(right)
+                .map_or(Inverse::NoLeft, Inverse::Left))
+        }
+    }
 
-                    is_true_inc_concat(goal, Some(add_result), equation)
-                        || is_true_inc_concat(goal, Some(mul_result), equation)
-                        || is_true_inc_concat(goal, Some(concat_result), equation)
-                }
+    ///Concatenation (||) joins the digits of the left and right numbers, so 12 || 345 = 12345.
+    ///Inverted by stripping the digits of right from the end of the result.
+    pub struct Concat;
+
+    impl<N: Number> Operator<N> for Concat {
+        fn symbol(&self) -> &str {
+            "||"
+        }
+
+        fn apply(&self, left: N, right: N) -> Option<N> {
+            //0 || right is just right, even when right has as many digits as N can hold
+            if left == N::ZERO {
+                return Some(right);
             }
-            None => {
-                //If current_value was None then the results of concat,add, or multiply are just next_operand itself
-                if equation_over {
-                    goal == next_operand
+            //Note that concating num2 to num1 is effectively = num1 * (10**number of digits in num2) + num2.
+            left.checked_mul(right.digit_shift()?)?.checked_add(right)
+        }
+
+        fn invert(&self, result: N, right: N) -> Result<Inverse<N>, Overflow> {
+            let Some(shift) = right.digit_shift() else {
+                //right has as many digits as N can hold, so only 0 || right fits in N
+                return Ok(if result == right {
+                    Inverse::Left(N::ZERO)
                 } else {
-                    if goal < next_operand {
-                        return false;
+                    Inverse::NoLeft
+                });
+            };
+
+            if result.checked_rem(shift) == Some(right) {
+                //result = left * shift + right
+                Ok(result
+                    .checked_sub(right)
+                    .and_then(|shifted| shifted.checked_exact_div(shift))
+                    .map_or(Inverse::NoLeft, Inverse::Left))
+            } else {
+                Ok(Inverse::NoLeft)
+            }
+        }
+    }
+
+    ///The operators available in part 1
+    pub fn part1<N: Number>() -> [&'static dyn Operator<N>; 2] {
+        [&Add, &Mul]
+    }
+
+    ///The operators available in part 2
+    pub fn part2<N: Number>() -> [&'static dyn Operator<N>; 3] {
+        [&Add, &Mul, &Concat]
+    }
+
+    ///An equation such as 190: 10 19 over numbers of type N. Operators are always evaluated left-to-right.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Equation<N: Number = usize> {
+        pub result: N,
+        pub operands: Vec<N>,
+    }
+
+    ///Parse a number of type N, returning Err(Overflow) if it is too large for N
+    fn parse_number<N: Number>(raw: &str) -> Result<N, Overflow> {
+        raw.parse::<N>().map_err(|error| match error.kind() {
+            IntErrorKind::PosOverflow => Overflow,
+            _ => panic!("invalid number {:?} in equation", raw),
+        })
+    }
+
+    impl<N: Number> Equation<N> {
+        ///Parse an equation from a line like 190: 10 19.
+        ///Returns Err(Overflow) if one of the numbers is too large for N.
+        pub fn parse(line: &str) -> Result<Equation<N>, Overflow> {
+            let (result, operands) = line.split_once(":").expect("equation should contain ':'");
+            let result = parse_number(result)?;
+            //equations look like 190: 10 19 (split_whitespace takes care of the space after the colon)
+            let operands: Vec<N> = operands
+                .split_whitespace()
+                .map(parse_number)
+                .collect::<Result<_, _>>()?;
+
+            if operands.is_empty() {
+                panic!("An equation should contain at least one number")
+            }
+
+            Ok(Equation { result, operands })
+        }
+
+        ///Call on_solution for every assignment of operators (one between each pair of operands)
+        ///that makes the equation true, stopping early if on_solution returns false.
+        ///Returns false if we stopped early. Sets overflowed if some branch could not be followed because
+        ///an intermediate value does not fit in N.
+        ///
+        ///We go right-to-left: goal is what the first operand_num operands must evaluate to, and assignment holds
+        ///the operators chosen for the operands after those (in reverse order).
+        fn search<'a>(
+            &self,
+            operators: &[&'a dyn Operator<N>],
+            goal: N,
+            operand_num: usize,
+            assignment: &mut Vec<&'a dyn Operator<N>>,
+            overflowed: &mut bool,
+            on_solution: &mut impl FnMut(Vec<&'a dyn Operator<N>>) -> bool,
+        ) -> bool {
+            if operand_num == 1 {
+                return self.operands[0] != goal
+                    || on_solution(assignment.iter().rev().copied().collect());
+            }
+
+            let last_operand = self.operands[operand_num - 1];
+            for &operator in operators {
+                //Short circuting: only operators that can produce the goal from the last operand are worth
+                //following (for example * only if the last operand divides the goal)
+                let keep_going = match operator.invert(goal, last_operand) {
+                    Ok(Inverse::Left(left_goal)) => {
+                        assignment.push(operator);
+                        let keep_going = self.search(
+                            operators,
+                            left_goal,
+                            operand_num - 1,
+                            assignment,
+                            overflowed,
+                            on_solution,
+                        );
+                        assignment.pop();
+                        keep_going
+                    }
+                    //There is nothing to prune with, so every way of evaluating the operands before is a solution
+                    Ok(Inverse::AnyLeft) => {
+                        assignment.push(operator);
+                        let keep_going = self.search_forward(
+                            operators,
+                            self.operands[0],
+                            &mut vec![],
+                            assignment,
+                            overflowed,
+                            on_solution,
+                        );
+                        assignment.pop();
+                        keep_going
+                    }
+                    Ok(Inverse::NoLeft) => true,
+                    Err(Overflow) => {
+                        *overflowed = true;
+                        true
                     }
+                };
+                if !keep_going {
+                    return false;
+                }
+            }
+            true
+        }
+
+        ///Call on_solution for every assignment of operators to the operands before the ones assignment is for
+        ///that can be evaluated (to any value), followed by the operators in assignment (which is in reverse order,
+        ///like in search). We go left-to-right: value is what the first prefix.len() + 1 operands evaluated to.
+        ///Returns false if we stopped early, and sets overflowed if some value could not be evaluated in N.
+        fn search_forward<'a>(
+            &self,
+            operators: &[&'a dyn Operator<N>],
+            value: N,
+            prefix: &mut Vec<&'a dyn Operator<N>>,
+            assignment: &[&'a dyn Operator<N>],
+            overflowed: &mut bool,
+            on_solution: &mut impl FnMut(Vec<&'a dyn Operator<N>>) -> bool,
+        ) -> bool {
+            let operand_num = self.operands.len() - assignment.len();
+            let next_operand = prefix.len() + 1;
+            if next_operand == operand_num {
+                let solution = prefix
+                    .iter()
+                    .copied()
+                    .chain(assignment.iter().rev().copied())
+                    .collect();
+                return on_solution(solution);
+            }
 
-                    is_true_inc_concat(goal, Some(next_operand), equation)
+            for &operator in operators {
+                match operator.apply(value, self.operands[next_operand]) {
+                    Some(next_value) => {
+                        prefix.push(operator);
+                        let keep_going = self.search_forward(
+                            operators,
+                            next_value,
+                            prefix,
+                            assignment,
+                            overflowed,
+                            on_solution,
+                        );
+                        prefix.pop();
+                        if !keep_going {
+                            return false;
+                        }
+                    }
+                    None => *overflowed = true,
                 }
             }
+            true
         }
-    }
-}
-///Returns the sum of the results of all true equations
-fn solution_part1(file_path: &str) -> usize {
-    let mut sum = 0;
 
-    for line in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-    {
-        let (result, equation) = line.split_once(":").expect("equation should contain ':'");
-        let result = result.parse::<usize>().unwrap();
-        let equation = equation.trim(); //equations look like 190: 10 19 so a trim is needed.
+        ///Returns an assignment of operators (one between each pair of operands) that makes the equation true,
+        ///or None if there is no such assignment.
+        ///Returns Err(Overflow) if there is no such assignment in N, but there might be one with a larger number type.
+        pub fn solve<'a>(
+            &self,
+            operators: &[&'a dyn Operator<N>],
+        ) -> Result<Option<Vec<&'a dyn Operator<N>>>, Overflow> {
+            let mut solution = None;
+            let mut overflowed = false;
+            self.search(
+                operators,
+                self.result,
+                self.operands.len(),
+                &mut vec![],
+                &mut overflowed,
+                &mut |assignment| {
+                    solution = Some(assignment);
+                    false
+                },
+            );
 
-        let (first_num, equation) = equation
-            .split_once(" ")
-            .expect("An equation should contain at least two numbers");
+            match solution {
+                None if overflowed => Err(Overflow),
+                solution => Ok(solution),
+            }
+        }
 
-        //It is ok we intialize current value like this as we know equations contain at least 2 numbers
-        let current_value = first_num.parse::<usize>().unwrap();
+        ///Returns the number of distinct assignments of operators that make the equation true.
+        ///Returns Err(Overflow) if some assignments could not be checked in N.
+        pub fn count_solutions(&self, operators: &[&dyn Operator<N>]) -> Result<usize, Overflow> {
+            let mut counter = 0;
+            let mut overflowed = false;
+            self.search(
+                operators,
+                self.result,
+                self.operands.len(),
+                &mut vec![],
+                &mut overflowed,
+                &mut |_| {
+                    counter += 1;
+                    true
+                },
+            );
 
-        if equation::is_true(result, current_value, equation) {
-            sum += result;
+            if overflowed {
+                Err(Overflow)
+            } else {
+                Ok(counter)
+            }
+        }
+
+        ///Returns whether the equation can evaluate to the goal if we select correct operations
+        pub fn is_true(&self, operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+            Ok(self.solve(operators)?.is_some())
+        }
+
+        ///Evaluate the operands left-to-right with the given operators.
+        ///Returns None if some intermediate value is not a valid number (for example it overflows).
+        pub fn evaluate(&self, assignment: &[&dyn Operator<N>]) -> Option<N> {
+            if assignment.len() + 1 != self.operands.len() {
+                panic!("there should be exactly one operator between each pair of operands")
+            }
+
+            assignment
+                .iter()
+                .zip(&self.operands[1..])
+                .try_fold(self.operands[0], |value, (operator, &operand)| {
+                    operator.apply(value, operand)
+                })
+        }
+
+        ///Render the equation with the given operators, like 190 = 10 * 19
+        pub fn render(&self, assignment: &[&dyn Operator<N>]) -> String {
+            if assignment.len() + 1 != self.operands.len() {
+                panic!("there should be exactly one operator between each pair of operands")
+            }
+
+            let mut rendered = format!("{} = {}", self.result, self.operands[0]);
+            for (operator, operand) in assignment.iter().zip(&self.operands[1..]) {
+                rendered.push_str(&format!(" {} {}", operator.symbol(), operand));
+            }
+            rendered
         }
     }
-    sum
-}
 
-///Returns the sum of the results of all true equations (now accounting for || as well)
-fn solution_part2(file_path: &str) -> usize {
-    let mut sum = 0;
+    ///Solve the equation on line with usize numbers, falling back to u128 numbers if the equation
+    ///(or some intermediate value) does not fit in a usize.
+    ///operators and wide_operators should be the same operators for usize and for u128.
+    ///
+    ///Returns the result of the equation (as a u128) if it can be made true and None if it can't.
+    ///Returns Err(Overflow) if it doesn't fit in u128 either.
+    pub fn true_result(
+        line: &str,
+        operators: &[&dyn Operator<usize>],
+        wide_operators: &[&dyn Operator<u128>],
+    ) -> Result<Option<u128>, Overflow> {
+        let narrow = Equation::<usize>::parse(line).and_then(|equation| {
+            Ok(equation
+                .is_true(operators)?
+                .then_some(equation.result as u128))
+        });
 
-    for line in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-    {
-        let (result, equation) = line.split_once(":").expect("equation should contain ':'");
-        let result = result.parse::<usize>().unwrap();
-        let equation = equation.trim(); //equations look like 190: 10 19 so a trim is needed.
+        match narrow {
+            Err(Overflow) => {
+                let equation = Equation::<u128>::parse(line)?;
+                Ok(equation.is_true(wide_operators)?.then_some(equation.result))
+            }
+            narrow => narrow,
+        }
+    }
+}
 
-        if equation::is_true_inc_concat(result, None, equation) {
-            sum += result;
+///Returns the sum of the results of all the equations that can be made true with the given operators
+///(see equation::true_result)
+fn sum_true_equations(
+    input: &str,
+    operators: &[&dyn equation::Operator<usize>],
+    wide_operators: &[&dyn equation::Operator<u128>],
+) -> Result<u128, equation::Overflow> {
+    let mut sum: u128 = 0;
+    for line in input.lines() {
+        if let Some(result) = equation::true_result(line, operators, wide_operators)? {
+            sum = sum.checked_add(result).ok_or(equation::Overflow)?;
         }
     }
-    sum
+    Ok(sum)
+}
+
+///Returns the sum of the results of all true equations
+fn solution_part1(file_path: &str) -> u128 {
+    let input = fs::read_to_string(file_path).expect("failed to open file");
+    sum_true_equations(&input, &equation::part1(), &equation::part1())
+        .expect("equations should fit in 128 bits")
+}
+
+///Returns the sum of the results of all true equations (now accounting for || as well)
+fn solution_part2(file_path: &str) -> u128 {
+    let input = fs::read_to_string(file_path).expect("failed to open file");
+    sum_true_equations(&input, &equation::part2(), &equation::part2())
+        .expect("equations should fit in 128 bits")
 }
 
 #[cfg(test)]
 mod tests {
 
+    use super::equation::{
+        Equation, Inverse, Number, Operator, Overflow, part1, part2, true_result,
+    };
     use super::*;
 
+    const EXAMPLE: &str = "190: 10 19
+3267: 81 40 27
+83: 17 5
+156: 15 6
+7290: 6 8 6 15
+161011: 16 10 13
+192: 17 8 14
+21037: 9 7 18 13
+292: 11 6 16 20";
+
     #[test]
     fn answer() {
         dbg!(solution_part1("puzzle_inputs/day7.txt"));
@@ -151,4 +511,161 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day7example.txt");
         assert_eq!(result, 11387);
     }
+
+    #[test]
+    fn operator_assignments() {
+        assert_eq!(sum_true_equations(EXAMPLE, &part1(), &part1()), Ok(3749));
+        assert_eq!(sum_true_equations(EXAMPLE, &part2(), &part2()), Ok(11387));
+
+        let equation = Equation::<usize>::parse("190: 10 19").unwrap();
+        let solution = equation.solve(&part1()).unwrap().unwrap();
+        assert_eq!(equation.render(&solution), "190 = 10 * 19");
+
+        //3267 = 81 + 40 * 27 = 81 * 40 + 27
+        let equation = Equation::<usize>::parse("3267: 81 40 27").unwrap();
+        assert_eq!(equation.count_solutions(&part1()), Ok(2));
+
+        let equation = Equation::<usize>::parse("7290: 6 8 6 15").unwrap();
+        assert_eq!(
+            equation.solve(&part1()).map(|solution| solution.is_none()),
+            Ok(true)
+        );
+        let solution = equation.solve(&part2()).unwrap().unwrap();
+        assert_eq!(equation.render(&solution), "7290 = 6 * 8 || 6 * 15");
+        assert_eq!(equation.evaluate(&solution), Some(7290));
+    }
+
+    ///Subtraction (-), to check the solver works with operators it doesn't know about
+    struct Sub;
+
+    impl<N: Number> Operator<N> for Sub {
+        fn symbol(&self) -> &str {
+            "-"
+        }
+
+        fn apply(&self, left: N, right: N) -> Option<N> {
+            left.checked_sub(right)
+        }
+
+        fn invert(&self, result: N, right: N) -> Result<Inverse<N>, Overflow> {
+            //Unlike the other operators, the left operand is larger than the result
+            result.checked_add(right).map(Inverse::Left).ok_or(Overflow)
+        }
+    }
+
+    ///Exponentiation (^), inverted by taking an exact integer root
+    struct Pow;
+
+    impl Operator for Pow {
+        fn symbol(&self) -> &str {
+            "^"
+        }
+
+        fn apply(&self, left: usize, right: usize) -> Option<usize> {
+            left.checked_pow(right.try_into().ok()?)
+        }
+
+        fn invert(&self, result: usize, right: usize) -> Result<Inverse, Overflow> {
+            //Note left^0 is 1 for any left
+            if right == 0 {
+                return Ok(if result == 1 {
+                    Inverse::AnyLeft
+                } else {
+                    Inverse::NoLeft
+                });
+            }
+            let root = (result as f64).powf(1.0 / right as f64).round() as usize;
+            Ok((root.saturating_sub(1)..=root + 1)
+                .find(|&left| self.apply(left, right) == Some(result))
+                .map_or(Inverse::NoLeft, Inverse::Left))
+        }
+    }
+
+    #[test]
+    fn user_defined_operators() {
+        let operators: [&dyn Operator; 4] =
+            [&super::equation::Add, &super::equation::Mul, &Sub, &Pow];
+
+        let equation = Equation::<usize>::parse("19: 3 2 10").unwrap();
+        let solution = equation.solve(&operators).unwrap().unwrap();
+        assert_eq!(equation.render(&solution), "19 = 3 ^ 2 + 10");
+
+        //(10 - 4) * 2 = 12, and 10 + 4 - 2 = 12
+        let equation = Equation::<usize>::parse("12: 10 4 2").unwrap();
+        assert_eq!(equation.count_solutions(&operators), Ok(2));
+        assert_eq!(equation.count_solutions(&part2()), Ok(0));
+
+        //Anything to the power of 0 is 1, so 3 + 5 ^ 0, 3 * 5 ^ 0 and 3 ^ 5 ^ 0 are all 1
+        let equation = Equation::<usize>::parse("1: 3 5 0").unwrap();
+        assert_eq!(
+            equation.count_solutions(&[&super::equation::Add, &super::equation::Mul, &Pow]),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn zero_operands() {
+        //Anything times 0 is 0
+        let equation = Equation::<usize>::parse("0: 5 0").unwrap();
+        assert_eq!(equation.count_solutions(&part1()), Ok(1));
+        //0 + 0, 0 * 0 and 0 || 0
+        let equation = Equation::<usize>::parse("0: 0 0").unwrap();
+        assert_eq!(equation.count_solutions(&part1()), Ok(2));
+        assert_eq!(equation.count_solutions(&part2()), Ok(3));
+        //3 + 4 and 3 * 4 can both be multiplied by 0
+        let equation = Equation::<usize>::parse("0: 3 4 0").unwrap();
+        assert_eq!(equation.count_solutions(&part1()), Ok(2));
+        let solution = equation.solve(&part1()).unwrap().unwrap();
+        assert_eq!(equation.render(&solution), "0 = 3 + 4 * 0");
+        assert_eq!(equation.evaluate(&solution), Some(0));
+
+        //0 || right is right, even when right has too many digits to shift anything in front of it
+        let equation =
+            Equation::<usize>::parse("18446744073709551615: 0 18446744073709551615").unwrap();
+        assert_eq!(equation.count_solutions(&part2()), Ok(2));
+        let solution = equation
+            .solve(&[&super::equation::Concat])
+            .unwrap()
+            .unwrap();
+        assert_eq!(equation.evaluate(&solution), Some(18446744073709551615));
+    }
+
+    #[test]
+    fn wide_numbers() {
+        //The result doesn't fit in a usize (on 64 bit targets)
+        let line = "36893488147419103230: 18446744073709551615 2";
+        assert_eq!(Equation::<usize>::parse(line), Err(Overflow));
+        assert_eq!(
+            true_result(line, &part1(), &part1()),
+            Ok(Some(36893488147419103230))
+        );
+
+        //Concatenating two 20 digit numbers gives 40 digits, which doesn't fit in a u128 either
+        let line =
+            "1844674407370955161518446744073709551615: 18446744073709551615 18446744073709551615";
+        assert_eq!(true_result(line, &part2(), &part2()), Err(Overflow));
+        //while 23 digits do
+        let line = "18446744073709551615123: 18446744073709551615 123";
+        assert_eq!(
+            true_result(line, &part2(), &part2()),
+            Ok(Some(18446744073709551615123))
+        );
+        assert_eq!(true_result(line, &part1(), &part1()), Ok(None));
+
+        //Everything fits in a usize except the intermediate value 18446744073709551615 + 10
+        let line = "10: 18446744073709551615 10 18446744073709551615";
+        let equation = Equation::<usize>::parse(line).unwrap();
+        assert!(matches!(
+            equation.solve(&[&super::equation::Add, &Sub]),
+            Err(Overflow)
+        ));
+        assert_eq!(
+            true_result(
+                line,
+                &[&super::equation::Add, &Sub],
+                &[&super::equation::Add, &Sub]
+            ),
+            Ok(Some(10))
+        );
+    }
 }
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::fs;
 
 //part 1 solution notes:
@@ -20,218 +19,396 @@ use std::fs;
 //and how you travel from B to A repeated starting from A. Look at the example on the website to see this.
 //Actually, we can simply use a set instead of this blank map.
 
+//Generalizing: travelling from B by k times the way from A to B puts us at a point where A is (k+1)/k times
+//as far as B. So part 1 is k = 1, part 2 is every whole k (and k = 0, the antennas themselves).
+//An AntinodeRule describes which k's to use.
+
 mod input {
     use itertools::Itertools;
     use std::collections::{HashMap, HashSet};
 
-    #[derive(Debug)]
-    pub struct InputMap {
-        pub signal_locations: HashMap<char, Vec<(usize, usize)>>,
-        pub row_num: usize,
-        pub col_num: usize,
+    ///A positive rational number numerator/denominator
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Ratio {
+        pub numerator: usize,
+        pub denominator: usize,
     }
 
-    impl InputMap {
-        pub fn count_unique_antinode_locations(&self) -> usize {
-            //We use a set because we don't want to double count locations
-            let mut antinode_locations: HashSet<(usize, usize)> = HashSet::new();
-
-            for (_signal, location_list) in self.signal_locations.iter() {
-                if location_list.len() < 2 {
-                    //1 antenna can't create an antinode
-                    continue;
-                }
-
-                //given a Signal A, iterate over all combinations of 2 antennas of signal A
-                for (&loc_1, &loc_2) in location_list.iter().tuple_combinations::<(_, _)>() {
-                    self.add_antiondes(loc_1, loc_2, &mut antinode_locations);
-                }
+    impl Ratio {
+        pub fn new(numerator: usize, denominator: usize) -> Ratio {
+            if numerator == 0 || denominator == 0 {
+                panic!("a ratio must be positive")
+            }
+            Ratio {
+                numerator,
+                denominator,
             }
-
-            //So now our Hashset contains all the in-bounds locations of antinodes
-            antinode_locations.len()
         }
 
-        ///Given the locations of 2 antennas of signal A, add the antinodes that are in bounds to the HashSet of locations
-        fn add_antiondes(
-            &self,
-            mut loc_1: (usize, usize),
-            mut loc_2: (usize, usize),
-            set: &mut HashSet<(usize, usize)>,
-        ) {
-            //a convinence for later (we make sure loc_1.0 <= loc_2.0)
-            if loc_1.0 > loc_2.0 {
-                (loc_1, loc_2) = (loc_2, loc_1);
-            }
+        pub fn whole(number: usize) -> Ratio {
+            Ratio::new(number, 1)
+        }
+    }
 
-            let row_diff = loc_2.0 - loc_1.0;
+    ///What a single step between antennas is
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Step {
+        ///The whole way from one antenna to the other (the puzzle's semantics). Locations on the line between
+        ///the steps are skipped, for example with antennas 2 apart only every second location on their line counts.
+        Separation,
+        ///The way from one antenna to the other divided by its gcd, which is the smallest step between locations
+        ///on their line. Steps are taken from each antenna in both directions, so the locations between the
+        ///antennas are reached too (with every harmonic this is every location in line of sight of both antennas).
+        Lattice,
+    }
 
-            //so we want to add row_diff to loc_2 and subtract it from loc_1
+    ///Where antinodes appear for a pair of antennas A and B of the same frequency.
+    ///
+    ///For every ratio k in ratios (and every harmonic h = 1, 2, ... up to max_harmonic) there is an antinode at
+    ///B + h * k * (B - A) and at A + h * k * (A - B), as long as that is a location on the map (so fractional
+    ///ratios only give an antinode when the step lands exactly on a location).
+    ///For example the antinode at B + (B - A) is where A is twice as far as B.
+    ///With Step::Lattice, B - A is replaced by the smallest step along the line (see Step).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct AntinodeRule {
+        pub ratios: Vec<Ratio>,
+        pub step: Step,
+        ///None means every harmonic that stays in the bounds of the map
+        pub max_harmonic: Option<usize>,
+        ///Whether the antennas themselves are antinodes (for frequencies with at least 2 antennas)
+        pub include_antennas: bool,
+    }
 
-            if loc_1.1 <= loc_2.1 {
-                let col_diff: usize = loc_2.1 - loc_1.1;
+    impl AntinodeRule {
+        ///Part 1: an antinode wherever one antenna is exactly twice as far as the other
+        pub fn part1() -> AntinodeRule {
+            AntinodeRule {
+                ratios: vec![Ratio::whole(1)],
+                step: Step::Separation,
+                max_harmonic: Some(1),
+                include_antennas: false,
+            }
+        }
 
-                //so we want to add col_diff to loc_2 and subtract it from loc_1
+        ///Part 2: an antinode at every whole multiple of the distance between the antennas (including the antennas)
+        pub fn part2() -> AntinodeRule {
+            AntinodeRule {
+                ratios: vec![Ratio::whole(1)],
+                step: Step::Separation,
+                max_harmonic: None,
+                include_antennas: true,
+            }
+        }
 
-                if row_diff <= loc_1.0 && col_diff <= loc_1.1 {
-                    //we know it ok to do this subtraction (we stay in bounds)
-                    set.insert((loc_1.0 - row_diff, loc_1.1 - col_diff));
-                }
+        ///Every location exactly in line with at least two antennas of the same frequency (geometrically),
+        ///which unlike part 2 includes the locations between two antennas and those skipped by a long step
+        pub fn line_of_sight() -> AntinodeRule {
+            AntinodeRule {
+                ratios: vec![Ratio::whole(1)],
+                step: Step::Lattice,
+                max_harmonic: None,
+                include_antennas: true,
+            }
+        }
+    }
 
-                let potential = (loc_2.0 + row_diff, loc_2.1 + col_diff);
-                if potential.0 < self.row_num && potential.1 < self.col_num {
-                    set.insert(potential);
-                }
-            } else {
-                let col_diff: usize = loc_1.1 - loc_2.1;
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
 
-                //so we want to subtract col_diff from loc_2 and add it to loc_1
+    #[derive(Debug)]
+    pub struct InputMap {
+        pub signal_locations: HashMap<char, Vec<(usize, usize)>>,
+        pub row_num: usize,
+        pub col_num: usize,
+    }
 
-                if row_diff <= loc_1.0 && loc_1.1 + col_diff < self.col_num {
-                    set.insert((loc_1.0 - row_diff, loc_1.1 + col_diff));
+    impl InputMap {
+        ///Parse the input map, where each char other than '.' is an antenna of that frequency
+        pub fn parse(input: &str) -> InputMap {
+            //A Hash Map of signal type as the key, and a vector of locations of that signal antennas as the value
+            let mut signal_locations: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
+
+            for (row_index, line) in input.lines().enumerate() {
+                for (col_index, char) in line.chars().enumerate() {
+                    if char != '.' {
+                        //for each point on the input map, we update our hashmap accordingly
+                        signal_locations
+                            .entry(char)
+                            .or_default()
+                            .push((row_index, col_index));
+                    }
                 }
+            }
 
-                if loc_2.0 + row_diff < self.row_num && col_diff <= loc_2.1 {
-                    set.insert((loc_2.0 + row_diff, loc_2.1 - col_diff));
-                }
+            //the data is guaranteed to be rectanguler
+            InputMap {
+                signal_locations,
+                row_num: input.lines().count(),
+                col_num: input.lines().next().map_or(0, |line| line.chars().count()),
             }
         }
 
-        pub fn count_unique_antinode_locations_part2(&self) -> usize {
-            //We use a set because we don't want to double count locations
-            let mut antinode_locations: HashSet<(usize, usize)> = HashSet::new();
+        ///Returns the in-bounds antinode locations of each frequency according to rule
+        pub fn antinodes(&self, rule: &AntinodeRule) -> HashMap<char, HashSet<(usize, usize)>> {
+            let mut antinodes = HashMap::new();
 
-            for (_signal, location_list) in self.signal_locations.iter() {
-                if location_list.len() < 2 {
-                    //1 antenna can't create an antinode
-                    continue;
-                }
+            for (&signal, location_list) in self.signal_locations.iter() {
+                //We use a set because we don't want to double count locations
+                let mut antinode_locations: HashSet<(usize, usize)> = HashSet::new();
 
-                //given a Signal A, iterate over all combinations of 2 antennas of signal A
-                for (&loc_1, &loc_2) in location_list.iter().tuple_combinations::<(_, _)>() {
-                    self.add_antiondes_part2(loc_1, loc_2, &mut antinode_locations);
+                if location_list.len() >= 2 {
+                    //given a Signal A, iterate over all combinations of 2 antennas of signal A
+                    for (&loc_1, &loc_2) in location_list.iter().tuple_combinations::<(_, _)>() {
+                        antinode_locations.extend(self.pair_antinodes(loc_1, loc_2, rule));
+                    }
                 }
+
+                //Note 1 antenna can't create an antinode, so such frequencies have an empty set
+                antinodes.insert(signal, antinode_locations);
             }
 
-            //So now our Hashset contains all the in-bounds locations of antinodes
-            antinode_locations.len()
+            antinodes
         }
 
-        ///Given the locations of 2 antennas of signal A, add the antinodes that are in bounds to the HashSet of locations.
-        ///The trick to part 2 is to notice it is nearly identical to part 1 and the answer follows from
-        ///pretending any new node we add to the HashSet of antinodes is its own antenna
-        fn add_antiondes_part2(
+        ///Returns the in-bounds antinodes created by a single pair of antennas of the same signal
+        fn pair_antinodes(
             &self,
-            mut loc_1: (usize, usize),
-            mut loc_2: (usize, usize),
+            loc_1: (usize, usize),
+            loc_2: (usize, usize),
+            rule: &AntinodeRule,
+        ) -> HashSet<(usize, usize)> {
+            let mut antinode_locations = HashSet::new();
+            if rule.include_antennas {
+                antinode_locations.insert(loc_1);
+                antinode_locations.insert(loc_2);
+            }
+            self.add_antinodes(loc_1, loc_2, rule, &mut antinode_locations);
+            self.add_antinodes(loc_2, loc_1, rule, &mut antinode_locations);
+            antinode_locations
+        }
+
+        ///Given the locations of 2 antennas of the same signal, add the antinodes beyond loc_2
+        ///(that is at loc_2 + h * k * (loc_2 - loc_1)) that are in bounds to the HashSet of locations.
+        ///With Step::Lattice the difference is reduced by its gcd and we also walk back from loc_2 (towards loc_1).
+        fn add_antinodes(
+            &self,
+            loc_1: (usize, usize),
+            loc_2: (usize, usize),
+            rule: &AntinodeRule,
             set: &mut HashSet<(usize, usize)>,
         ) {
-            //first off note that all antennas locations are also antinodes
-            set.insert(loc_1);
-            set.insert(loc_2);
-
-            //a convinence for later (we make sure loc_1.0 <= loc_2.0)
-            if loc_1.0 > loc_2.0 {
-                (loc_1, loc_2) = (loc_2, loc_1);
+            let mut row_diff = loc_2.0 as isize - loc_1.0 as isize;
+            let mut col_diff = loc_2.1 as isize - loc_1.1 as isize;
+
+            let directions: &[isize] = match rule.step {
+                Step::Separation => &[1],
+                Step::Lattice => {
+                    let divisor = gcd(row_diff.unsigned_abs(), col_diff.unsigned_abs()) as isize;
+                    row_diff /= divisor;
+                    col_diff /= divisor;
+                    &[1, -1]
+                }
+            };
+
+            for (ratio, direction) in rule.ratios.iter().cartesian_product(directions) {
+                let (numerator, denominator) =
+                    (ratio.numerator as isize, ratio.denominator as isize);
+
+                //We keep the location scaled up by denominator so fractional steps stay exact
+                let in_bounds = |scaled: isize, bound: usize| {
+                    scaled >= 0 && scaled <= (bound as isize - 1) * denominator
+                };
+
+                let mut harmonic = 1;
+                while rule
+                    .max_harmonic
+                    .is_none_or(|max_harmonic| harmonic <= max_harmonic)
+                {
+                    let multiple = direction * numerator * harmonic as isize;
+                    let scaled_row = loc_2.0 as isize * denominator + row_diff * multiple;
+                    let scaled_col = loc_2.1 as isize * denominator + col_diff * multiple;
+
+                    //Each harmonic is further away than the last, so once we leave the map we are done
+                    if !in_bounds(scaled_row, self.row_num) || !in_bounds(scaled_col, self.col_num)
+                    {
+                        break;
+                    }
+
+                    if scaled_row % denominator == 0 && scaled_col % denominator == 0 {
+                        set.insert((
+                            (scaled_row / denominator) as usize,
+                            (scaled_col / denominator) as usize,
+                        ));
+                    }
+
+                    harmonic += 1;
+                }
             }
+        }
 
-            let row_diff = loc_2.0 - loc_1.0;
+        ///Returns how many unique in-bounds locations contain an antinode (of any frequency) according to rule
+        pub fn count_unique_antinode_locations_with(&self, rule: &AntinodeRule) -> usize {
+            self.antinodes(rule)
+                .into_values()
+                .flatten()
+                .collect::<HashSet<_>>()
+                .len()
+        }
 
-            //so we want to add row_diff to loc_2 and subtract it from loc_1
+        pub fn count_unique_antinode_locations(&self) -> usize {
+            self.count_unique_antinode_locations_with(&AntinodeRule::part1())
+        }
 
-            if loc_1.1 <= loc_2.1 {
-                let col_diff: usize = loc_2.1 - loc_1.1;
+        pub fn count_unique_antinode_locations_part2(&self) -> usize {
+            self.count_unique_antinode_locations_with(&AntinodeRule::part2())
+        }
+    }
 
-                //so we want to add col_diff to loc_2 and subtract it from loc_1
-                let mut current = loc_1;
+    ///A map of antennas that can be changed one antenna at a time.
+    ///For each location we keep how many antenna pairs put an antinode there, so adding or removing an antenna
+    ///only needs to look at the pairs it is a part of (rather than recalculating everything).
+    #[derive(Debug)]
+    pub struct AntennaField {
+        map: InputMap,
+        rule: AntinodeRule,
+        reference_counts: HashMap<(usize, usize), usize>,
+    }
 
-                //We now add as many other antinodes in this line as possible while staying in bounds
-                while row_diff <= current.0 && col_diff <= current.1 {
-                    current = (current.0 - row_diff, current.1 - col_diff);
-                    set.insert(current);
+    impl AntennaField {
+        pub fn new(map: InputMap, rule: AntinodeRule) -> AntennaField {
+            let mut field = AntennaField {
+                map: InputMap {
+                    signal_locations: HashMap::new(),
+                    row_num: map.row_num,
+                    col_num: map.col_num,
+                },
+                rule,
+                reference_counts: HashMap::new(),
+            };
+
+            for (signal, location_list) in map.signal_locations {
+                for location in location_list {
+                    field
+                        .add_antenna(signal, location)
+                        .expect("the parsed map has valid antennas");
                 }
+            }
 
-                let mut current = loc_2;
-                while current.0 + row_diff < self.row_num && current.1 + col_diff < self.col_num {
-                    current = (current.0 + row_diff, current.1 + col_diff);
-                    set.insert(current);
-                }
-            } else {
-                let col_diff: usize = loc_1.1 - loc_2.1;
+            field
+        }
 
-                //so we want to subtract col_diff from loc_2 and add it to loc_1
+        pub fn map(&self) -> &InputMap {
+            &self.map
+        }
 
-                let mut current = loc_1;
+        ///Returns the signal of the antenna at location, if there is one
+        pub fn antenna_at(&self, location: (usize, usize)) -> Option<char> {
+            self.map
+                .signal_locations
+                .iter()
+                .find(|(_, location_list)| location_list.contains(&location))
+                .map(|(&signal, _)| signal)
+        }
 
-                while row_diff <= current.0 && current.1 + col_diff < self.col_num {
-                    current = (current.0 - row_diff, current.1 + col_diff);
-                    set.insert(current);
+        pub fn add_antenna(
+            &mut self,
+            signal: char,
+            location: (usize, usize),
+        ) -> Result<(), &'static str> {
+            if location.0 >= self.map.row_num || location.1 >= self.map.col_num {
+                return Err("location is out of bounds");
+            }
+            if self.antenna_at(location).is_some() {
+                return Err("there is already an antenna in this location");
+            }
+
+            for &other in self.map.signal_locations.get(&signal).into_iter().flatten() {
+                for antinode in self.map.pair_antinodes(other, location, &self.rule) {
+                    *self.reference_counts.entry(antinode).or_default() += 1;
                 }
+            }
+            self.map
+                .signal_locations
+                .entry(signal)
+                .or_default()
+                .push(location);
 
-                let mut current = loc_2;
+            Ok(())
+        }
+
+        ///Removes the antenna at location, returning its signal
+        pub fn remove_antenna(&mut self, location: (usize, usize)) -> Result<char, &'static str> {
+            let signal = self
+                .antenna_at(location)
+                .ok_or("there is no antenna in this location")?;
+
+            let location_list = self
+                .map
+                .signal_locations
+                .get_mut(&signal)
+                .expect("antenna_at found this signal");
+            location_list.retain(|&other| other != location);
+            if location_list.is_empty() {
+                self.map.signal_locations.remove(&signal);
+            }
 
-                while current.0 + row_diff < self.row_num && col_diff <= current.1 {
-                    current = (current.0 + row_diff, current.1 - col_diff);
-                    set.insert(current);
+            for &other in self.map.signal_locations.get(&signal).into_iter().flatten() {
+                for antinode in self.map.pair_antinodes(other, location, &self.rule) {
+                    let count = self
+                        .reference_counts
+                        .get_mut(&antinode)
+                        .expect("every antinode of a pair was counted");
+                    *count -= 1;
+                    if *count == 0 {
+                        self.reference_counts.remove(&antinode);
+                    }
                 }
             }
+
+            Ok(signal)
         }
-    }
-}
 
-///Returns how many unique locations within the bounds of the map contain an antinode
-fn solution_part1(file_path: &str) -> usize {
-    //A Hash Map of signal type as the key, and a vector of locations of that signal antennas as the value
-    let mut signal_locations: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
-
-    let mut row_num = 0;
-    let mut col_num = 0;
-
-    for (line_num, line) in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-        .inspect(|_| {
-            //count the number of rows
-            row_num += 1;
-        })
-        .enumerate()
-    {
-        if line_num == 0 {
-            for (col_num, char) in line
-                .chars()
-                .inspect(|_| {
-                    //count the number of cols, the data is guaranteed to be rectanguler,
-                    //so we only need to count this once
-                    col_num += 1;
-                })
-                .enumerate()
-            {
-                if char != '.' {
-                    //for each point on the input map, we update our hashmap accordingly
-                    signal_locations
-                        .entry(char)
-                        .and_modify(|vec: &mut Vec<(usize, usize)>| vec.push((line_num, col_num)))
-                        .or_insert(vec![(line_num, col_num)]);
-                }
+        ///Moves the antenna at from to the location to (keeping its signal)
+        pub fn move_antenna(
+            &mut self,
+            from: (usize, usize),
+            to: (usize, usize),
+        ) -> Result<(), &'static str> {
+            if to.0 >= self.map.row_num || to.1 >= self.map.col_num {
+                return Err("location is out of bounds");
             }
-        } else {
-            for (col_num, char) in line.chars().enumerate() {
-                if char != '.' {
-                    //for each point on the input map, we update our hashmap accordingly
-                    signal_locations
-                        .entry(char)
-                        .and_modify(|vec: &mut Vec<(usize, usize)>| vec.push((line_num, col_num)))
-                        .or_insert(vec![(line_num, col_num)]);
-                }
+            if from != to && self.antenna_at(to).is_some() {
+                return Err("there is already an antenna in this location");
             }
+
+            let signal = self.remove_antenna(from)?;
+            self.add_antenna(signal, to)
+        }
+
+        ///How many antenna pairs have an antinode in location
+        pub fn reference_count(&self, location: (usize, usize)) -> usize {
+            self.reference_counts.get(&location).copied().unwrap_or(0)
+        }
+
+        pub fn is_antinode(&self, location: (usize, usize)) -> bool {
+            self.reference_counts.contains_key(&location)
+        }
+
+        pub fn antinodes(&self) -> impl Iterator<Item = &(usize, usize)> {
+            self.reference_counts.keys()
+        }
+
+        pub fn count_unique_antinode_locations(&self) -> usize {
+            self.reference_counts.len()
         }
     }
+}
 
-    let input_map = input::InputMap {
-        signal_locations,
-        row_num,
-        col_num,
-    };
+///Returns how many unique locations within the bounds of the map contain an antinode
+fn solution_part1(file_path: &str) -> usize {
+    let input_map =
+        input::InputMap::parse(&fs::read_to_string(file_path).expect("failed to open file"));
 
     input_map.count_unique_antinode_locations()
 }
@@ -239,57 +416,8 @@ fn solution_part1(file_path: &str) -> usize {
 ///Returns how many unique locations within the bounds of the map contain an antinode.
 /// Note the distance doesn't matter now.
 fn solution_part2(file_path: &str) -> usize {
-    //A Hash Map of signal type as the key, and a vector of locations of that signal antennas as the value
-    let mut signal_locations: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
-
-    let mut row_num = 0;
-    let mut col_num = 0;
-
-    for (line_num, line) in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .lines()
-        .inspect(|_| {
-            //count the number of rows
-            row_num += 1;
-        })
-        .enumerate()
-    {
-        if line_num == 0 {
-            for (col_num, char) in line
-                .chars()
-                .inspect(|_| {
-                    //count the number of cols, the data is guaranteed to be rectanguler,
-                    //so we only need to count this once
-                    col_num += 1;
-                })
-                .enumerate()
-            {
-                if char != '.' {
-                    //for each point on the input map, we update our hashmap accordingly
-                    signal_locations
-                        .entry(char)
-                        .and_modify(|vec: &mut Vec<(usize, usize)>| vec.push((line_num, col_num)))
-                        .or_insert(vec![(line_num, col_num)]);
-                }
-            }
-        } else {
-            for (col_num, char) in line.chars().enumerate() {
-                if char != '.' {
-                    //for each point on the input map, we update our hashmap accordingly
-                    signal_locations
-                        .entry(char)
-                        .and_modify(|vec: &mut Vec<(usize, usize)>| vec.push((line_num, col_num)))
-                        .or_insert(vec![(line_num, col_num)]);
-                }
-            }
-        }
-    }
-
-    let input_map = input::InputMap {
-        signal_locations,
-        row_num,
-        col_num,
-    };
+    let input_map =
+        input::InputMap::parse(&fs::read_to_string(file_path).expect("failed to open file"));
 
     input_map.count_unique_antinode_locations_part2()
 }
@@ -297,7 +425,23 @@ fn solution_part2(file_path: &str) -> usize {
 #[cfg(test)]
 mod tests {
 
+    use super::input::{AntennaField, AntinodeRule, InputMap, Ratio, Step};
     use super::*;
+    use itertools::Itertools;
+    use std::collections::HashSet;
+
+    const EXAMPLE: &str = "............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............";
 
     #[test]
     fn answer() {
@@ -316,4 +460,147 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day8example.txt");
         assert_eq!(result, 34);
     }
+
+    #[test]
+    fn antinode_rules() {
+        let input_map = InputMap::parse(EXAMPLE);
+        assert_eq!(input_map.count_unique_antinode_locations(), 14);
+        assert_eq!(input_map.count_unique_antinode_locations_part2(), 34);
+
+        let antinodes = input_map.antinodes(&AntinodeRule::part1());
+        assert_eq!(antinodes[&'0'].len(), 10);
+        assert_eq!(antinodes[&'A'].len(), 5);
+
+        //Two antennas 2 columns apart
+        let input_map = InputMap::parse("...........\n....a.a....");
+        let rule = |ratios: Vec<Ratio>, max_harmonic, include_antennas| AntinodeRule {
+            ratios,
+            step: Step::Separation,
+            max_harmonic,
+            include_antennas,
+        };
+        let columns = |rule: &AntinodeRule| {
+            let mut columns: Vec<usize> = input_map.antinodes(rule)[&'a']
+                .iter()
+                .map(|&(_, col_index)| col_index)
+                .collect();
+            columns.sort();
+            columns
+        };
+
+        assert_eq!(columns(&AntinodeRule::part1()), vec![2, 8]);
+        assert_eq!(columns(&AntinodeRule::part2()), vec![0, 2, 4, 6, 8, 10]);
+        assert_eq!(
+            columns(&rule(vec![Ratio::whole(1)], Some(2), false)),
+            vec![0, 2, 8, 10]
+        );
+        //half the distance lands between the antennas' columns, a third never lands exactly on a location
+        assert_eq!(
+            columns(&rule(vec![Ratio::new(1, 2)], Some(1), true)),
+            vec![3, 4, 6, 7]
+        );
+        assert_eq!(
+            columns(&rule(vec![Ratio::new(1, 3)], Some(2), false)),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            columns(&rule(vec![Ratio::new(1, 2), Ratio::whole(2)], None, false)),
+            vec![0, 1, 2, 3, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn incremental_antenna_field() {
+        let mut field = AntennaField::new(InputMap::parse(EXAMPLE), AntinodeRule::part1());
+        assert_eq!(field.count_unique_antinode_locations(), 14);
+        //The A antennas at (5, 6) and (9, 9) put an antinode at (1, 3), and so do the 0 antennas at (2, 5) and (3, 7)
+        assert_eq!(field.reference_count((1, 3)), 2);
+        assert_eq!(field.reference_count((7, 7)), 1);
+        assert_eq!(field.reference_count((0, 0)), 0);
+
+        //Removing an antenna and adding it back gets us to where we started
+        assert_eq!(field.remove_antenna((5, 6)), Ok('A'));
+        assert_eq!(
+            field.remove_antenna((5, 6)),
+            Err("there is no antenna in this location")
+        );
+        assert_eq!(
+            field.add_antenna('A', (9, 9)),
+            Err("there is already an antenna in this location")
+        );
+        assert_eq!(
+            field.add_antenna('A', (12, 0)),
+            Err("location is out of bounds")
+        );
+        field.add_antenna('A', (5, 6)).unwrap();
+        assert_eq!(field.count_unique_antinode_locations(), 14);
+
+        //After every change the field agrees with rebuilding everything from scratch
+        let moves = [
+            ((1, 8), (0, 0)),
+            ((9, 9), (3, 3)),
+            ((0, 0), (11, 11)),
+            ((3, 3), (3, 3)),
+        ];
+        let mut grid: Vec<Vec<char>> = EXAMPLE.lines().map(|line| line.chars().collect()).collect();
+        for (from, to) in moves {
+            field.move_antenna(from, to).unwrap();
+            let signal = grid[from.0][from.1];
+            grid[from.0][from.1] = '.';
+            grid[to.0][to.1] = signal;
+
+            let rebuilt = grid
+                .iter()
+                .map(|row| row.iter().collect::<String>())
+                .join("\n");
+            let rebuilt = InputMap::parse(&rebuilt);
+            let mut expected: Vec<_> = rebuilt
+                .antinodes(&AntinodeRule::part1())
+                .into_values()
+                .flatten()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            let mut actual: Vec<_> = field.antinodes().copied().collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn line_of_sight() {
+        //The example has no pair of antennas with a common divisor in their difference, so nothing changes
+        let input_map = InputMap::parse(EXAMPLE);
+        assert_eq!(
+            input_map.count_unique_antinode_locations_with(&AntinodeRule::line_of_sight()),
+            34
+        );
+
+        //Antennas at (0, 0) and (2, 4) have (1, 2) and (3, 6) on their line as well
+        let input_map = InputMap::parse("b......\n.......\n....b..\n.......\n.......");
+        let sorted = |rule: &AntinodeRule| {
+            let mut locations: Vec<_> = input_map.antinodes(rule)[&'b'].iter().copied().collect();
+            locations.sort();
+            locations
+        };
+
+        //The puzzle's part 2 steps over them...
+        assert_eq!(sorted(&AntinodeRule::part2()), vec![(0, 0), (2, 4)]);
+        //...but geometrically every one of them is in line with both antennas
+        assert_eq!(
+            sorted(&AntinodeRule::line_of_sight()),
+            vec![(0, 0), (1, 2), (2, 4), (3, 6)]
+        );
+
+        //A single lattice step from each antenna, in both directions
+        let rule = AntinodeRule {
+            ratios: vec![Ratio::whole(1)],
+            step: Step::Lattice,
+            max_harmonic: Some(1),
+            include_antennas: false,
+        };
+        assert_eq!(sorted(&rule), vec![(1, 2), (3, 6)]);
+        assert_eq!(sorted(&AntinodeRule::part1()), Vec::<(usize, usize)>::new());
+    }
 }
@@ -1,12 +1,114 @@
-use std::{fs, iter};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fs, iter,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MemoryBlock {
     File { file_id: usize },
     Free,
 }
+
+///A file (or a piece of a file) on the disk followed by some free space
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub file_id: usize,
+    pub length: usize,
+    pub free_after: usize,
+}
+
+///The disk as a sequence of runs, rather than one entry per block (so huge disks don't need to be materialized).
+///Note a file can be split across several runs (after compaction).
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DiskMap {
-    data: Vec<MemoryBlock>,
+    runs: Vec<Run>,
+}
+
+///How many moves a defragmentation took, and how many blocks (bytes) were moved in total
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Moves {
+    pub moves: usize,
+    pub blocks_moved: usize,
+}
+
+impl Moves {
+    fn record(&mut self, blocks: usize) {
+        self.moves += 1;
+        self.blocks_moved += blocks;
+    }
+}
+
+///How fragmented a disk map is after a defragmentation, and how much work that took
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DefragMetrics {
+    ///How many files are split into more than one piece
+    pub fragmented_files: usize,
+    pub largest_free_extent: usize,
+    pub moves: usize,
+    pub blocks_moved: usize,
+}
+
+///Which free block a file moves to when moving whole files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fit {
+    ///The leftmost free block the file fits in (the amphipod's **NEW** process)
+    First,
+    ///The smallest free block the file fits in (the leftmost of those if there are a few)
+    Best,
+    ///The largest free block (the leftmost of those if there are a few)
+    Worst,
+}
+
+///A way to defragment a disk map
+pub trait DefragStrategy {
+    fn name(&self) -> &'static str;
+
+    ///Returns the defragmented disk map and the moves it took
+    fn defrag(&self, disk_map: &DiskMap) -> (DiskMap, Moves);
+}
+
+///The amphipod's process: move single blocks from the end of the disk to the leftmost free block
+pub struct MoveBlocks;
+
+///Move whole files (right to left, each at most once) to a free block to the left of them
+pub struct MoveWholeFiles(pub Fit);
+
+///Make every file contiguous with all the files in order of file_ID at the start of the disk
+pub struct FullDefrag;
+
+impl DefragStrategy for MoveBlocks {
+    fn name(&self) -> &'static str {
+        "move blocks"
+    }
+
+    fn defrag(&self, disk_map: &DiskMap) -> (DiskMap, Moves) {
+        disk_map.compact_blocks()
+    }
+}
+
+impl DefragStrategy for MoveWholeFiles {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            Fit::First => "first fit",
+            Fit::Best => "best fit",
+            Fit::Worst => "worst fit",
+        }
+    }
+
+    fn defrag(&self, disk_map: &DiskMap) -> (DiskMap, Moves) {
+        disk_map.compact_whole_files(self.0)
+    }
+}
+
+impl DefragStrategy for FullDefrag {
+    fn name(&self) -> &'static str {
+        "full defrag"
+    }
+
+    fn defrag(&self, disk_map: &DiskMap) -> (DiskMap, Moves) {
+        disk_map.full_defrag()
+    }
 }
 
 #[derive(Debug)]
@@ -15,216 +117,508 @@ struct FreeBlock {
     size: usize,
 }
 
+///The free blocks of a disk map keyed by their size, so we can quickly find the leftmost free block
+///that a file fits in (rather than scanning all of them)
+#[derive(Debug, Default)]
+struct FreeSpaceIndex {
+    ///For each size, a min-heap of the start indices of the free blocks of that size
+    by_size: BTreeMap<usize, BinaryHeap<Reverse<usize>>>,
+}
+
+impl FreeSpaceIndex {
+    fn new(free_blocks: Vec<FreeBlock>) -> FreeSpaceIndex {
+        let mut index = FreeSpaceIndex::default();
+        for free_block in free_blocks {
+            index.insert(free_block);
+        }
+        index
+    }
+
+    fn insert(&mut self, free_block: FreeBlock) {
+        if free_block.size > 0 {
+            self.by_size
+                .entry(free_block.size)
+                .or_default()
+                .push(Reverse(free_block.start_index));
+        }
+    }
+
+    ///Takes size blocks from a free block (chosen according to fit) of at least that size that starts before
+    ///before_index, returning where they start. What is left of that free block stays in the index.
+    fn take(&mut self, size: usize, before_index: usize, fit: Fit) -> Option<usize> {
+        //The leftmost free block of each size that's large enough (and before before_index).
+        //Note for a disk map from the dense format there are at most 9 sizes
+        let mut candidates = self
+            .by_size
+            .range(size..)
+            .filter_map(|(&block_size, heap)| {
+                heap.peek().map(|&Reverse(start)| (block_size, start))
+            })
+            .filter(|&(_, start_index)| start_index < before_index);
+
+        let (block_size, _) = match fit {
+            Fit::First => candidates.min_by_key(|&(_, start_index)| start_index),
+            Fit::Best => candidates.next(),
+            Fit::Worst => candidates.next_back(),
+        }?;
+
+        let heap = self.by_size.get_mut(&block_size).expect("we just found it");
+        let Reverse(start_index) = heap.pop().expect("we just peeked it");
+        if heap.is_empty() {
+            self.by_size.remove(&block_size);
+        }
+
+        self.insert(FreeBlock {
+            start_index: start_index + size,
+            size: block_size - size,
+        });
+
+        Some(start_index)
+    }
+}
+
 impl DiskMap {
-    ///Takes as input an explicit disk map.
-    ///Compacts the amphipod's hard drive using the process they requested.
-    pub fn build_and_compact(mut disk_map: Vec<MemoryBlock>) -> DiskMap {
-        //Compressing the disk map
-        let mut left_index = 0; //The current first occurence of free memory (i.e. '.') in the vector
-        'Compress: for index in (0..disk_map.len()).rev() {
-            //Note there is no situation in which we move a file to the right, so we only care when disk_map[index] != '.'
-            if let MemoryBlock::File { .. } = disk_map[index] {
-                //Find the next occurence of '.' in the vector
-                left_index = loop {
-                    if left_index < index {
-                        if let MemoryBlock::Free = disk_map[left_index] {
-                            break left_index;
-                        }
-                        left_index += 1;
-                    } else {
-                        //meaning there are no more '.' in the vector so compression is done
-                        break 'Compress;
-                    }
+    ///Takes as input the dense disk map, where digits alternate between the length of a file and
+    ///the length of the free space after it (and the file_ID is the index of the file).
+    pub fn parse(input: &str) -> DiskMap {
+        DiskMap::parse_with_file_ids(input, 0..).expect("the input should be a dense disk map")
+    }
+
+    ///Like parse, but the file_ID of each file is taken from file_ids (in order) rather than being its index.
+    ///This is how to read back a disk map written by to_dense.
+    pub fn parse_with_file_ids(
+        input: &str,
+        file_ids: impl IntoIterator<Item = usize>,
+    ) -> Result<DiskMap, &'static str> {
+        let digits = input
+            .trim()
+            .chars()
+            .map(|char| {
+                char.to_digit(10)
+                    .map(|digit| digit as usize)
+                    .ok_or("char should be a digit from 0-9")
+            })
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        let mut file_ids = file_ids.into_iter();
+        let runs = digits
+            .chunks(2)
+            .map(|chunk| {
+                Ok(Run {
+                    file_id: file_ids
+                        .next()
+                        .ok_or("there are more files than file IDs")?,
+                    length: chunk[0],
+                    //the last file might not have free space after it
+                    free_after: chunk.get(1).copied().unwrap_or(0),
+                })
+            })
+            .collect::<Result<Vec<Run>, &'static str>>()?;
+
+        Ok(DiskMap { runs })
+    }
+
+    ///Returns the dense disk map, along with the file_ID of each file in it
+    ///(as after compaction a file's file_ID is no longer its index).
+    ///A file longer than 9 blocks is written as several files with no free space in between, and more than 9 free
+    ///blocks are written with empty files in between, so the result has the same blocks but not the same runs.
+    pub fn to_dense(&self) -> (String, Vec<usize>) {
+        let mut digits: Vec<usize> = Vec::new();
+        let mut file_ids: Vec<usize> = Vec::new();
+
+        for run in &self.runs {
+            let mut length = run.length;
+            let mut free_after = run.free_after;
+            loop {
+                let piece_length = length.min(9);
+                let piece_free = if piece_length == length {
+                    free_after.min(9)
+                } else {
+                    0
                 };
-                disk_map.swap(left_index, index);
-                left_index += 1; //Since disk_map[left_index] != '.'
+                length -= piece_length;
+                free_after -= piece_free;
+
+                file_ids.push(run.file_id);
+                digits.extend([piece_length, piece_free]);
+
+                if length == 0 && free_after == 0 {
+                    break;
+                }
             }
         }
 
-        //Note that after the for loop our disk_map vector is a sequence of digits and then a bunch of '.' .
-        //Also note that left_index = the last index in the Vector that is not '.' .
-        //while we could do disk_map.truncate(left_index + 1), we preserve the entire data.
-        DiskMap { data: disk_map }
+        //Like the puzzle input, we leave out no free space at the end
+        if digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        let dense = digits
+            .into_iter()
+            .map(|digit| char::from_digit(digit as u32, 10).expect("every digit is at most 9"))
+            .collect();
+        (dense, file_ids)
     }
 
-    ///Returns the resulting filesystem checksum
-    pub fn checksum(&self) -> usize {
-        self.data
-            .iter()
-            .enumerate()
-            .take_while(|(_, entry)| **entry != MemoryBlock::Free)
-            .map(|(index, entry)| {
-                let MemoryBlock::File { file_id } = *entry else {
-                    panic!("impossible as entry must be a File");
-                };
-                index * file_id
+    ///Renders the explicit disk map like the puzzle does, a file block as its file_ID and a free block as '.'.
+    ///Note file_IDs of 10 or more only show their last digit.
+    pub fn render(&self) -> String {
+        self.blocks()
+            .map(|block| match block {
+                MemoryBlock::File { file_id } => {
+                    char::from_digit((file_id % 10) as u32, 10).expect("a digit is less than 10")
+                }
+                MemoryBlock::Free => '.',
             })
+            .collect()
+    }
+
+    pub fn runs(&self) -> &[Run] {
+        &self.runs
+    }
+
+    ///The total number of blocks (files and free space) on the disk
+    pub fn size(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|run| run.length + run.free_after)
             .sum()
     }
 
-    ///Create a helper vector of where FreeBlocks are in the diskmap and what their size is
-    fn find_free_blocks(disk_map: &[MemoryBlock]) -> Vec<FreeBlock> {
-        let mut free_blocks: Vec<FreeBlock> = Vec::new();
+    ///Returns the explicit disk map one block at a time (lazily)
+    pub fn blocks(&self) -> impl Iterator<Item = MemoryBlock> + '_ {
+        self.runs.iter().flat_map(|run| {
+            iter::repeat_n(
+                MemoryBlock::File {
+                    file_id: run.file_id,
+                },
+                run.length,
+            )
+            .chain(iter::repeat_n(MemoryBlock::Free, run.free_after))
+        })
+    }
 
-        let mut new_free_block = true;
-        let mut size = 0;
-        let mut start_index = 0;
+    ///Push a run of a file to the end of runs, merging it with the previous run if that is the same file with
+    ///no free space in between
+    fn push_run(runs: &mut Vec<Run>, run: Run) {
+        match runs.last_mut() {
+            Some(last) if last.file_id == run.file_id && last.free_after == 0 => {
+                last.length += run.length;
+                last.free_after = run.free_after;
+            }
+            _ => runs.push(run),
+        }
+    }
 
-        //Note we chain a meaningless file to the end of the iterator so we guarantee our iterator ends with a file
-        for (index, entry) in disk_map
-            .iter()
-            .chain(iter::once(&MemoryBlock::File { file_id: 42 }))
-            .enumerate()
-        {
-            if let MemoryBlock::Free = *entry {
-                if new_free_block {
-                    start_index = index;
-                    size = 1;
-                    new_free_block = false;
-                } else {
-                    size += 1;
-                }
-            } else if !new_free_block {
-                //meaning this is a file encountered after a free block.
-                //So when we next encounter a '.' it will denote a new free_block
-                new_free_block = true;
+    ///Compacts the amphipod's hard drive using the process they requested.
+    pub fn compact(&self) -> DiskMap {
+        self.compact_blocks().0
+    }
+
+    ///Returns the disk map defragmented using strategy, and metrics on how that went
+    pub fn defrag(&self, strategy: &dyn DefragStrategy) -> (DiskMap, DefragMetrics) {
+        let (disk_map, moves) = strategy.defrag(self);
+        let metrics = DefragMetrics {
+            fragmented_files: disk_map.fragmented_files(),
+            largest_free_extent: disk_map.largest_free_extent(),
+            moves: moves.moves,
+            blocks_moved: moves.blocks_moved,
+        };
+        (disk_map, metrics)
+    }
 
-                //bank the old free block
-                free_blocks.push(FreeBlock { start_index, size });
+    ///Returns how many files are split into more than one piece
+    pub fn fragmented_files(&self) -> usize {
+        let mut pieces: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut previous: Option<&Run> = None;
+
+        //empty files have no pieces (and don't separate the pieces around them)
+        for run in self.runs.iter().filter(|run| run.length > 0) {
+            let continues_previous = previous.is_some_and(|previous| {
+                previous.file_id == run.file_id && previous.free_after == 0
+            });
+            if !continues_previous {
+                *pieces.entry(run.file_id).or_default() += 1;
             }
+            previous = Some(run);
         }
 
-        free_blocks
+        pieces.into_values().filter(|&count| count > 1).count()
     }
 
-    ///Takes as input an explicit disk map.
-    ///Compacts the amphipod's hard drive using the **NEW** process they requested.
-    pub fn build_and_compact_part2(mut disk_map: Vec<MemoryBlock>) -> DiskMap {
-        //First we create a helper vector of where FreeBlocks are and what their size is
-        let mut free_blocks = DiskMap::find_free_blocks(&disk_map);
-
-        //Compressing the disk map
-        let mut right_index = if disk_map.is_empty() {
-            panic!("The disk map is blank")
-        } else {
-            disk_map.len() - 1
+    ///Returns the size of the largest stretch of free blocks
+    pub fn largest_free_extent(&self) -> usize {
+        let mut largest = 0;
+        let mut current = 0;
+        for run in &self.runs {
+            if run.length > 0 {
+                current = 0;
+            }
+            current += run.free_after;
+            largest = largest.max(current);
+        }
+        largest
+    }
+
+    fn compact_blocks(&self) -> (DiskMap, Moves) {
+        let mut moves = Moves::default();
+        let Some(last_run) = self.runs.len().checked_sub(1) else {
+            return (self.clone(), moves);
         };
 
-        'Compress: while right_index > 0 {
-            if let MemoryBlock::File { file_id } = disk_map[right_index] {
-                //Find the size of this file
-                let mut file_size = 0;
+        let mut runs: Vec<Run> = Vec::new();
+
+        //We fill the free space from the left with blocks taken from the file at right_index (from its end)
+        let mut right_index = last_run;
+        let mut right_remaining = self.runs[right_index].length; //how many blocks of that file haven't moved
+        for left_index in 0..self.runs.len() {
+            let left = self.runs[left_index];
+
+            if left_index == right_index {
+                //what is left of the last file we took blocks from doesn't move
+                DiskMap::push_run(
+                    &mut runs,
+                    Run {
+                        file_id: left.file_id,
+                        length: right_remaining,
+                        free_after: 0,
+                    },
+                );
+                break;
+            }
 
-                let current_file = MemoryBlock::File { file_id };
+            //Note there is no situation in which we move a file to the right, so the file at left_index stays
+            DiskMap::push_run(
+                &mut runs,
+                Run {
+                    free_after: 0,
+                    ..left
+                },
+            );
+
+            let mut free = left.free_after;
+            while free > 0 && right_index > left_index {
+                let moved = free.min(right_remaining);
+                if moved > 0 {
+                    moves.record(moved);
+                    DiskMap::push_run(
+                        &mut runs,
+                        Run {
+                            file_id: self.runs[right_index].file_id,
+                            length: moved,
+                            free_after: 0,
+                        },
+                    );
+                }
+                free -= moved;
+                right_remaining -= moved;
 
-                while current_file == disk_map[right_index] {
-                    file_size += 1;
-                    if right_index == 0 {
-                        break 'Compress;
-                    }
+                if right_remaining == 0 {
                     right_index -= 1;
+                    right_remaining = self.runs[right_index].length;
                 }
-                //Note right_index+1 is the index of where the file starts
-                let file_start_index = right_index + 1;
-
-                //Find the next occurence of free space of sufficent size in the vector **To the LEFT** of the file
-                //and move the file there
-                for free_block in free_blocks.iter_mut() {
-                    //We must move the file left, and we know free_blocks is ordered so we can do short-circuting
-                    if free_block.start_index > file_start_index {
-                        break;
-                    }
+            }
 
-                    if free_block.size >= file_size {
-                        //Move the file to the free space
-                        for idx in 0..file_size {
-                            disk_map.swap(free_block.start_index + idx, file_start_index + idx);
-                        }
+            if right_index == left_index {
+                //meaning there are no more files to the right so compression is done
+                break;
+            }
+        }
+
+        //Now our disk map is a sequence of files and then a bunch of free space.
+        //We preserve the size of the disk by putting all of that free space after the last run.
+        let used: usize = runs.iter().map(|run| run.length).sum();
+        if let Some(last) = runs.last_mut() {
+            last.free_after = self.size() - used;
+        }
 
-                        //Update this free block accordingly
-                        free_block.size -= file_size;
-                        free_block.start_index += file_size;
+        (DiskMap { runs }, moves)
+    }
 
-                        //Since we found a free space, no need to keep searching
-                        break;
-                    }
+    ///Returns the start index of each run
+    fn run_starts(&self) -> Vec<usize> {
+        self.runs
+            .iter()
+            .scan(0, |start_index, run| {
+                let run_start = *start_index;
+                *start_index += run.length + run.free_after;
+                Some(run_start)
+            })
+            .collect()
+    }
+
+    ///Create a helper vector of where FreeBlocks are in the diskmap and what their size is.
+    ///Free space on either side of an empty file is a single FreeBlock.
+    fn find_free_blocks(&self) -> Vec<FreeBlock> {
+        let mut free_blocks: Vec<FreeBlock> = Vec::new();
+
+        for (run, run_start) in self.runs.iter().zip(self.run_starts()) {
+            let start_index = run_start + run.length;
+            match free_blocks.last_mut() {
+                Some(free_block)
+                    if run.length == 0 && free_block.start_index + free_block.size == run_start =>
+                {
+                    free_block.size += run.free_after;
                 }
-            } else {
-                right_index -= 1;
+                _ if run.free_after > 0 => free_blocks.push(FreeBlock {
+                    start_index,
+                    size: run.free_after,
+                }),
+                _ => (),
             }
         }
 
-        DiskMap { data: disk_map }
+        free_blocks
     }
 
-    ///Returns the resulting filesystem checksum
-    pub fn checksum_part2(&self) -> usize {
-        self.data
-            .iter()
-            .enumerate()
-            .filter(|(_, entry)| **entry != MemoryBlock::Free)
-            .map(|(index, entry)| {
-                let MemoryBlock::File { file_id } = *entry else {
-                    panic!("impossible as entry must be a File");
-                };
-                index * file_id
-            })
-            .sum()
+    ///Builds the disk map from where each file starts, given as (start_index, run),
+    ///where the free_after of each run is ignored (it is determined by where the next file starts)
+    fn from_placements(mut placements: Vec<(usize, Run)>, size: usize) -> DiskMap {
+        placements.sort_by_key(|&(start_index, run)| (start_index, run.length));
+
+        let mut runs: Vec<Run> = Vec::with_capacity(placements.len());
+        for (index, &(start_index, run)) in placements.iter().enumerate() {
+            let next_start = placements
+                .get(index + 1)
+                .map_or(size, |&(next_start, _)| next_start);
+            runs.push(Run {
+                free_after: next_start - start_index - run.length,
+                ..run
+            });
+        }
+
+        DiskMap { runs }
     }
-}
 
-fn solution_part1(file_path: &str) -> usize {
-    //Note char_index can be a multiple digit number which is why we use MemoryBlock
+    ///Compacts the amphipod's hard drive using the **NEW** process they requested.
+    pub fn compact_part2(&self) -> DiskMap {
+        self.compact_whole_files(Fit::First).0
+    }
+
+    ///Moves whole files (each run counts as a file) to the free block chosen by fit
+    fn compact_whole_files(&self, fit: Fit) -> (DiskMap, Moves) {
+        let mut moves = Moves::default();
+
+        //First we create a helper index of where FreeBlocks are and what their size is
+        let mut free_space = FreeSpaceIndex::new(self.find_free_blocks());
+
+        let mut placements: Vec<(usize, Run)> = Vec::with_capacity(self.runs.len());
+
+        //We attempt to move each file once, in order of decreasing file_ID (which is right to left)
+        for (run, file_start_index) in self.runs.iter().zip(self.run_starts()).rev() {
+            //Find free space of sufficent size **To the LEFT** of the file and move the file there.
+            //An empty file has no blocks so it can be anywhere, we put it at the start of the disk
+            //(where it can't end up inside a file that moved)
+            let new_start_index = match run.length {
+                0 => 0,
+                length => match free_space.take(length, file_start_index, fit) {
+                    Some(start_index) => {
+                        moves.record(length);
+                        start_index
+                    }
+                    None => file_start_index,
+                },
+            };
+
+            //Note the space the file leaves behind is never used, as every file left to move is to the left of it
+            placements.push((new_start_index, *run));
+        }
 
-    let mut explicit_disk_map: Vec<MemoryBlock> = Vec::new(); //An explict representation of the disk map.
+        (DiskMap::from_placements(placements, self.size()), moves)
+    }
 
-    for (char_index, char) in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .chars()
-        .enumerate()
-    {
-        let num_of_blocks = char.to_digit(10).expect("char should be a digit from 0-9") as usize;
+    ///Puts every file in one piece at the start of the disk, in order of file_ID.
+    ///Every piece of a file that isn't already where it needs to be counts as a move.
+    fn full_defrag(&self) -> (DiskMap, Moves) {
+        let mut moves = Moves::default();
+
+        //Where the pieces of each file are (in order)
+        let mut files: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+        for (run, start_index) in self.runs.iter().zip(self.run_starts()) {
+            files
+                .entry(run.file_id)
+                .or_default()
+                .push((start_index, run.length));
+        }
 
-        let block = if char_index % 2 == 0 {
-            //Note the file_ID is simply char_index/2.
-            MemoryBlock::File {
-                file_id: (char_index / 2),
+        let mut runs: Vec<Run> = Vec::with_capacity(files.len());
+        let mut start_index = 0;
+        for (file_id, mut pieces) in files {
+            pieces.sort();
+
+            let mut length = 0;
+            for (piece_start, piece_length) in pieces {
+                if piece_length > 0 && piece_start != start_index + length {
+                    moves.record(piece_length);
+                }
+                length += piece_length;
             }
-        } else {
-            MemoryBlock::Free
-        };
 
-        explicit_disk_map.extend(iter::repeat_n(block, num_of_blocks));
+            runs.push(Run {
+                file_id,
+                length,
+                free_after: 0,
+            });
+            start_index += length;
+        }
+
+        if let Some(last) = runs.last_mut() {
+            last.free_after = self.size() - start_index;
+        }
+
+        (DiskMap { runs }, moves)
     }
 
-    let disk_map = DiskMap::build_and_compact(explicit_disk_map);
-    disk_map.checksum()
-}
+    ///Returns the checksum of the file blocks at start_index..start_index + run.length,
+    ///or None if it doesn't fit in a usize
+    fn run_checksum(start_index: usize, run: &Run) -> Option<usize> {
+        //the sum of the indices start_index, ... , start_index + length - 1.
+        //We compute in u128 since length * (length - 1) overflows a usize long before the checksum does
+        let (start_index, length) = (start_index as u128, run.length as u128);
+        let index_sum = length
+            .checked_mul(start_index)?
+            .checked_add(length * length.saturating_sub(1) / 2)?;
+        usize::try_from(index_sum.checked_mul(run.file_id as u128)?).ok()
+    }
 
-fn solution_part2(file_path: &str) -> usize {
-    let mut explicit_disk_map: Vec<MemoryBlock> = Vec::new(); //An explict representation of the disk map.
-
-    for (char_index, char) in fs::read_to_string(file_path)
-        .expect("failed to open file")
-        .chars()
-        .enumerate()
-    {
-        let num_of_blocks = char.to_digit(10).expect("char should be a digit from 0-9") as usize;
-
-        let block = if char_index % 2 == 0 {
-            //Note the file_ID is simply char_index/2.
-            MemoryBlock::File {
-                file_id: (char_index / 2),
+    ///Returns the resulting filesystem checksum (of the files before the first free block),
+    ///or None if it doesn't fit in a usize
+    pub fn checksum(&self) -> Option<usize> {
+        let mut checksum: usize = 0;
+        for (run, start_index) in self.runs.iter().zip(self.run_starts()) {
+            checksum = checksum.checked_add(DiskMap::run_checksum(start_index, run)?)?;
+            if run.free_after > 0 {
+                break;
             }
-        } else {
-            MemoryBlock::Free
-        };
+        }
+        Some(checksum)
+    }
 
-        explicit_disk_map.extend(iter::repeat_n(block, num_of_blocks));
+    ///Returns the resulting filesystem checksum, or None if it doesn't fit in a usize
+    pub fn checksum_part2(&self) -> Option<usize> {
+        self.runs
+            .iter()
+            .zip(self.run_starts())
+            .try_fold(0_usize, |checksum, (run, start_index)| {
+                checksum.checked_add(DiskMap::run_checksum(start_index, run)?)
+            })
     }
+}
+
+fn solution_part1(file_path: &str) -> usize {
+    let disk_map = DiskMap::parse(&fs::read_to_string(file_path).expect("failed to open file"));
+    disk_map
+        .compact()
+        .checksum()
+        .expect("the checksum should fit in a usize")
+}
 
-    let disk_map = DiskMap::build_and_compact_part2(explicit_disk_map);
-    disk_map.checksum_part2()
+fn solution_part2(file_path: &str) -> usize {
+    let disk_map = DiskMap::parse(&fs::read_to_string(file_path).expect("failed to open file"));
+    disk_map
+        .compact_part2()
+        .checksum_part2()
+        .expect("the checksum should fit in a usize")
 }
 
 #[cfg(test)]
@@ -232,6 +626,8 @@ mod tests {
 
     use super::*;
 
+    const EXAMPLE: &str = "2333133121414131402";
+
     #[test]
     fn answer() {
         dbg!(solution_part1("puzzle_inputs/day9.txt"));
@@ -249,4 +645,320 @@ mod tests {
         let result = solution_part2("puzzle_inputs/day9example.txt");
         assert_eq!(result, 2858);
     }
+
+    #[test]
+    fn run_length_compaction() {
+        let disk_map = DiskMap::parse(EXAMPLE);
+        assert_eq!(
+            disk_map.render(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+
+        let compacted = disk_map.compact();
+        assert_eq!(
+            compacted.render(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(compacted.checksum(), Some(1928));
+
+        let compacted = disk_map.compact_part2();
+        assert_eq!(
+            compacted.render(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(compacted.checksum_part2(), Some(2858));
+
+        //An empty file doesn't split the free space around it, and nothing is materialized for huge files
+        let disk_map = DiskMap {
+            runs: vec![
+                Run {
+                    file_id: 0,
+                    length: 1,
+                    free_after: 2,
+                },
+                Run {
+                    file_id: 1,
+                    length: 0,
+                    free_after: 2,
+                },
+                Run {
+                    file_id: 2,
+                    length: 4,
+                    free_after: 0,
+                },
+            ],
+        };
+        assert_eq!(disk_map.compact_part2().render(), "02222....");
+        assert_eq!(disk_map.compact().render(), "02222....");
+
+        let huge = DiskMap {
+            runs: vec![
+                Run {
+                    file_id: 1,
+                    length: 1 << 40,
+                    free_after: 1 << 40,
+                },
+                Run {
+                    file_id: 2,
+                    length: 1 << 39,
+                    free_after: 0,
+                },
+            ],
+        };
+        let compacted = huge.compact();
+        assert_eq!(compacted.runs().len(), 2);
+        assert_eq!(compacted.size(), huge.size());
+        assert_eq!(compacted.runs(), huge.compact_part2().runs());
+
+        //The checksum of a huge file fits even though the square of its length doesn't
+        let huge = DiskMap {
+            runs: vec![
+                Run {
+                    file_id: 0,
+                    length: 1 << 33,
+                    free_after: 1,
+                },
+                Run {
+                    file_id: 1,
+                    length: 1,
+                    free_after: 0,
+                },
+            ],
+        };
+        assert_eq!(huge.compact().checksum(), Some(1 << 33));
+        assert_eq!(huge.checksum_part2(), Some((1 << 33) + 1));
+
+        //Each of these files has a checksum that fits, but all of them together don't
+        let huge = DiskMap {
+            runs: vec![
+                Run {
+                    file_id: 0,
+                    length: 1 << 40,
+                    free_after: 0,
+                },
+                Run {
+                    file_id: 1 << 23,
+                    length: 1,
+                    free_after: 0,
+                },
+                Run {
+                    file_id: (1 << 23) + 1,
+                    length: 1,
+                    free_after: 0,
+                },
+            ],
+        };
+        let checksums: Vec<Option<usize>> = huge
+            .runs()
+            .iter()
+            .zip(huge.run_starts())
+            .map(|(run, start_index)| DiskMap::run_checksum(start_index, run))
+            .collect();
+        assert!(checksums.iter().all(Option::is_some));
+        assert_eq!(huge.checksum(), None);
+        assert_eq!(huge.checksum_part2(), None);
+    }
+
+    ///The whole file compaction from before the FreeSpaceIndex, which scans every free block left to right.
+    ///Slow, but simple enough to check compact_part2 against.
+    fn first_fit_linear_scan(disk_map: &DiskMap) -> DiskMap {
+        let mut free_blocks = disk_map.find_free_blocks();
+
+        let mut placements: Vec<(usize, Run)> = Vec::with_capacity(disk_map.runs.len());
+        for (run, file_start_index) in disk_map.runs.iter().zip(disk_map.run_starts()).rev() {
+            let mut new_start_index = file_start_index;
+
+            for free_block in free_blocks.iter_mut() {
+                if free_block.start_index > file_start_index || run.length == 0 {
+                    break;
+                }
+
+                if free_block.size >= run.length {
+                    new_start_index = free_block.start_index;
+                    free_block.size -= run.length;
+                    free_block.start_index += run.length;
+                    break;
+                }
+            }
+
+            if run.length == 0 {
+                new_start_index = 0;
+            }
+            placements.push((new_start_index, *run));
+        }
+
+        DiskMap::from_placements(placements, disk_map.size())
+    }
+
+    #[test]
+    fn free_space_index() {
+        let mut free_space = FreeSpaceIndex::new(vec![
+            FreeBlock {
+                start_index: 2,
+                size: 1,
+            },
+            FreeBlock {
+                start_index: 5,
+                size: 3,
+            },
+            FreeBlock {
+                start_index: 10,
+                size: 9,
+            },
+        ]);
+
+        //The leftmost free block that fits, even though a later one is a better fit
+        assert_eq!(free_space.take(2, 30, Fit::First), Some(5));
+        //What is left of it (1 block at 7) is still used, but only before before_index
+        assert_eq!(free_space.take(1, 2, Fit::First), None);
+        assert_eq!(free_space.take(1, 30, Fit::First), Some(2));
+        assert_eq!(free_space.take(1, 30, Fit::First), Some(7));
+        assert_eq!(free_space.take(9, 10, Fit::First), None);
+        assert_eq!(free_space.take(9, 11, Fit::First), Some(10));
+        assert_eq!(free_space.take(1, 30, Fit::First), None);
+
+        //A long disk map (with every gap size) compacts quickly, and no space is lost
+        let dense: String = (0..20_000)
+            .map(|index| char::from_digit((index * 7 + index / 10) % 10, 10).unwrap())
+            .collect();
+        let disk_map = DiskMap::parse(&dense);
+        let compacted = disk_map.compact_part2();
+        assert_eq!(compacted.size(), disk_map.size());
+        let expected = first_fit_linear_scan(&disk_map);
+        assert_eq!(compacted.runs(), expected.runs());
+        assert_eq!(compacted.checksum_part2(), expected.checksum_part2());
+        //Files only ever move to the left
+        let starts: BTreeMap<usize, usize> = disk_map
+            .runs()
+            .iter()
+            .zip(disk_map.run_starts())
+            .map(|(run, start_index)| (run.file_id, start_index))
+            .collect();
+        for (run, start_index) in compacted.runs().iter().zip(compacted.run_starts()) {
+            assert!(run.length == 0 || start_index <= starts[&run.file_id]);
+        }
+    }
+
+    #[test]
+    fn defrag_strategies() {
+        let disk_map = DiskMap::parse(EXAMPLE);
+
+        let strategies: [&dyn DefragStrategy; 5] = [
+            &MoveBlocks,
+            &MoveWholeFiles(Fit::First),
+            &MoveWholeFiles(Fit::Best),
+            &MoveWholeFiles(Fit::Worst),
+            &FullDefrag,
+        ];
+        let results: Vec<(String, DefragMetrics)> = strategies
+            .iter()
+            .map(|strategy| {
+                let (defragmented, metrics) = disk_map.defrag(*strategy);
+                assert_eq!(defragmented.size(), disk_map.size());
+                (defragmented.render(), metrics)
+            })
+            .collect();
+
+        let metrics = |fragmented_files, largest_free_extent, moves, blocks_moved| DefragMetrics {
+            fragmented_files,
+            largest_free_extent,
+            moves,
+            blocks_moved,
+        };
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "0099811188827773336446555566..............".to_string(),
+                    metrics(2, 14, 7, 12)
+                ),
+                (
+                    "00992111777.44.333....5555.6666.....8888..".to_string(),
+                    metrics(0, 5, 4, 8)
+                ),
+                (
+                    "00992111777.44.333....5555.6666.....8888..".to_string(),
+                    metrics(0, 5, 4, 8)
+                ),
+                (
+                    "00992111777.44.333....5555.6666.....8888..".to_string(),
+                    metrics(0, 5, 4, 8)
+                ),
+                (
+                    "0011123334455556666777888899..............".to_string(),
+                    metrics(0, 14, 9, 26)
+                ),
+            ]
+        );
+
+        //On the example every whole file strategy does the same, but here they all pick a different free block
+        //for file 3
+        let disk_map = DiskMap::parse("1211131");
+        assert_eq!(disk_map.render(), "0..1.2...3");
+        let fits = [Fit::First, Fit::Best, Fit::Worst].map(|fit| {
+            let (defragmented, metrics) = disk_map.defrag(&MoveWholeFiles(fit));
+            (defragmented.render(), metrics.moves)
+        });
+        assert_eq!(
+            fits,
+            [
+                ("0321......".to_string(), 2),
+                ("021.3.....".to_string(), 3),
+                ("021...3...".to_string(), 3)
+            ]
+        );
+
+        //Defragmenting what the amphipods did with the first process puts the pieces of files back together
+        let (compacted, _) = disk_map.defrag(&MoveBlocks);
+        let (defragmented, metrics) = compacted.defrag(&FullDefrag);
+        assert_eq!(defragmented, disk_map.defrag(&FullDefrag).0);
+        assert_eq!(metrics.fragmented_files, 0);
+    }
+
+    #[test]
+    fn serialization() {
+        let disk_map = DiskMap::parse(EXAMPLE);
+        assert_eq!(
+            disk_map.to_dense(),
+            (EXAMPLE.to_string(), (0..10).collect())
+        );
+
+        //The worked examples from the puzzle
+        let small = DiskMap::parse("12345");
+        assert_eq!(small.render(), "0..111....22222");
+        assert_eq!(small.compact().render(), "022111222......");
+
+        //After compaction the file_IDs are out of order (and files are split), but we can still read it back
+        for compacted in [disk_map.compact(), disk_map.compact_part2()] {
+            let (dense, file_ids) = compacted.to_dense();
+            let rebuilt = DiskMap::parse_with_file_ids(&dense, file_ids).unwrap();
+            assert_eq!(rebuilt.render(), compacted.render());
+            assert_eq!(rebuilt.checksum_part2(), compacted.checksum_part2());
+        }
+        //The 14 free blocks at the end are written as 9, an empty file and then 5
+        assert_eq!(
+            disk_map.compact().to_dense(),
+            (
+                "2020103030103030102010402905".to_string(),
+                vec![0, 9, 8, 1, 8, 2, 7, 3, 6, 4, 6, 5, 6, 6]
+            )
+        );
+        let long_file = DiskMap {
+            runs: vec![Run {
+                file_id: 5,
+                length: 12,
+                free_after: 0,
+            }],
+        };
+        assert_eq!(long_file.to_dense(), ("903".to_string(), vec![5, 5]));
+
+        assert_eq!(
+            DiskMap::parse_with_file_ids("1a1", 0..),
+            Err("char should be a digit from 0-9")
+        );
+        assert_eq!(
+            DiskMap::parse_with_file_ids("121", [3]),
+            Err("there are more files than file IDs")
+        );
+    }
 }